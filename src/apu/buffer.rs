@@ -0,0 +1,80 @@
+// Fixed size queue of stereo samples between the APU and whatever plays them. When it fills
+// up the oldest samples are dropped, so nothing backs up if no frontend is draining it.
+pub struct SampleBuffer {
+    samples: Vec<[i16; 2]>,
+    // Index of the oldest sample
+    start: usize,
+    len: usize,
+}
+
+impl SampleBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: vec![[0, 0]; capacity],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.samples.len()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, sample: [i16; 2]) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+
+        let end = (self.start + self.len) % capacity;
+        self.samples[end] = sample;
+        if self.len == capacity {
+            self.start = (self.start + 1) % capacity;
+        }
+        else {
+            self.len += 1;
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<[i16; 2]> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let sample = self.samples[self.start];
+        self.start = (self.start + 1) % self.capacity();
+        self.len -= 1;
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_oldest_when_full() {
+        // Set up sample buffer for test
+        let mut buffer = SampleBuffer::new(3);
+        for value in 0..5 {
+            buffer.push([value, -value]);
+        }
+
+        // Run test and compare output
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.pop(), Some([2, -2]));
+        assert_eq!(buffer.pop(), Some([3, -3]));
+        assert_eq!(buffer.pop(), Some([4, -4]));
+        assert_eq!(buffer.is_empty(), true);
+        assert_eq!(buffer.pop(), None);
+    }
+}
@@ -0,0 +1,132 @@
+// Units shared between the channels
+
+// Turns a channel off once its length timer runs out, if enabled in NRx4. Clocked at 256Hz.
+pub struct LengthCounter {
+    pub enabled: bool,
+    counter: u16,
+    // 64 for most channels, 256 for the wave channel
+    max: u16,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> Self {
+        Self {
+            enabled: false,
+            counter: 0,
+            max,
+        }
+    }
+
+    // The length written to NRx1 counts up towards the maximum
+    pub fn load(&mut self, length: u8) {
+        self.counter = self.max - length as u16;
+    }
+
+    pub fn trigger(&mut self) {
+        if self.counter == 0 {
+            self.counter = self.max;
+        }
+    }
+
+    // Returns true when the counter has just run out and the channel should stop
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+}
+
+// Volume envelope set up by NRx2, clocked at 64Hz
+pub struct Envelope {
+    // NRx2 as last written, only takes effect on the next trigger
+    pub register: u8,
+    pub volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Self {
+            register: 0x00,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    // The top five bits of NRx2 power the channel's DAC, clearing them all switches it off
+    pub fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    fn period(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.period();
+    }
+
+    pub fn clock(&mut self) {
+        // A period of 0 stops the envelope
+        if self.period() == 0 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period();
+            if self.register & 0x08 != 0 && self.volume < 15 {
+                self.volume += 1;
+            }
+            else if self.register & 0x08 == 0 && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length_counter() {
+        // Set up length counter for test
+        let mut length = LengthCounter::new(64);
+        length.load(62);
+        length.enabled = true;
+
+        // Run test and compare output
+        assert_eq!(length.clock(), false);
+        assert_eq!(length.clock(), true);
+        assert_eq!(length.clock(), false);
+
+        // Triggering with the counter at 0 starts over from the maximum
+        length.trigger();
+        for _ in 0..63 {
+            assert_eq!(length.clock(), false);
+        }
+        assert_eq!(length.clock(), true);
+    }
+
+    #[test]
+    fn envelope() {
+        // Set up envelope for test, volume 2 going up every other clock
+        let mut envelope = Envelope::new();
+        envelope.register = 0x2A;
+        envelope.trigger();
+
+        // Run test and compare output
+        envelope.clock();
+        assert_eq!(envelope.volume, 2);
+        envelope.clock();
+        assert_eq!(envelope.volume, 3);
+        for _ in 0..40 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume, 15);
+    }
+}
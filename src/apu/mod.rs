@@ -0,0 +1,407 @@
+mod buffer;
+mod channel;
+mod noise;
+mod square;
+mod wave;
+
+pub use buffer::SampleBuffer;
+use noise::Noise;
+use square::Square;
+use wave::Wave;
+use crate::mmu::IoDevice;
+
+pub const NR10: u16 = 0xFF10;
+pub const NR11: u16 = 0xFF11;
+pub const NR12: u16 = 0xFF12;
+pub const NR13: u16 = 0xFF13;
+pub const NR14: u16 = 0xFF14;
+pub const NR21: u16 = 0xFF16;
+pub const NR22: u16 = 0xFF17;
+pub const NR23: u16 = 0xFF18;
+pub const NR24: u16 = 0xFF19;
+pub const NR30: u16 = 0xFF1A;
+pub const NR31: u16 = 0xFF1B;
+pub const NR32: u16 = 0xFF1C;
+pub const NR33: u16 = 0xFF1D;
+pub const NR34: u16 = 0xFF1E;
+pub const NR41: u16 = 0xFF20;
+pub const NR42: u16 = 0xFF21;
+pub const NR43: u16 = 0xFF22;
+pub const NR44: u16 = 0xFF23;
+pub const NR50: u16 = 0xFF24;
+pub const NR51: u16 = 0xFF25;
+pub const NR52: u16 = 0xFF26;
+pub const WAVE_RAM_START: u16 = 0xFF30;
+pub const WAVE_RAM_END: u16 = 0xFF3F;
+
+// T-cycles per second
+pub const CLOCK_RATE: u32 = 4_194_304;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+// The frame sequencer steps when this bit of the system counter (DIV bit 4) falls, at 512Hz
+const SEQUENCER_BIT: u16 = 0x1000;
+
+// How much of the high-pass filter's charge is kept each T-cycle
+const CHARGE_FACTOR: f32 = 0.999958;
+
+// Audio processing unit. Runs the four channels alongside the CPU and mixes them down into
+// stereo samples at the host sample rate.
+pub struct Apu {
+    // NR52 bit 7, everything but wave RAM is held in reset while it's off
+    powered: bool,
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+    nr50: u8,
+    nr51: u8,
+    // Next step of the frame sequencer, 0 - 7
+    sequencer_step: u8,
+    // Last seen value of the system counter bit that clocks the frame sequencer
+    sequencer_bit: bool,
+    sample_rate: u32,
+    // Adds up sample_rate every T-cycle, a sample is due each time it passes CLOCK_RATE
+    sample_clock: u32,
+    charge_factor: f32,
+    filter: HighPass,
+    pub samples: SampleBuffer,
+    // Each channel mixed on its own, only recorded once stems are enabled
+    pub stems: Option<[SampleBuffer; 4]>,
+    stem_filters: [HighPass; 4],
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        let mut apu = Self {
+            powered: false,
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            nr50: 0x00,
+            nr51: 0x00,
+            sequencer_step: 0,
+            sequencer_bit: false,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_clock: 0,
+            charge_factor: 0.0,
+            filter: HighPass::new(),
+            samples: SampleBuffer::new(0),
+            stems: None,
+            stem_filters: [HighPass::new(), HighPass::new(), HighPass::new(), HighPass::new()],
+        };
+        apu.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        apu
+    }
+
+    // The boot ROM hands over with channel 1 still on from the chime, its envelope long since run
+    // down to silence
+    pub fn finish_boot_chime(&mut self) {
+        self.square1.enabled = true;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Changes the host sample rate. The buffers are replaced with empty ones holding half a second.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.sample_clock = 0;
+        self.charge_factor = CHARGE_FACTOR.powf(CLOCK_RATE as f32 / sample_rate as f32);
+        self.samples = SampleBuffer::new(sample_rate as usize / 2);
+        if self.stems.is_some() {
+            self.enable_stems();
+        }
+    }
+
+    // Starts recording every channel into its own buffer alongside the mix
+    pub fn enable_stems(&mut self) {
+        let capacity = self.sample_rate as usize / 2;
+        self.stems = Some([0; 4].map(|_| SampleBuffer::new(capacity)));
+    }
+
+    // Advances the APU by a number of M-cycles. The frame sequencer is clocked from the system
+    // counter, so this takes the counter's value from before the timer was ticked.
+    pub fn tick(&mut self, cycles: u8, system_counter: u16) {
+        for cycle in 0..cycles as u16 {
+            if self.powered {
+                self.square1.step(4);
+                self.square2.step(4);
+                self.wave.step(4);
+                self.noise.step(4);
+
+                let counter = system_counter.wrapping_add((cycle + 1) * 4);
+                let sequencer_bit = counter & SEQUENCER_BIT != 0;
+                if self.sequencer_bit && !sequencer_bit {
+                    self.step_sequencer();
+                }
+                self.sequencer_bit = sequencer_bit;
+            }
+
+            self.sample_clock += self.sample_rate * 4;
+            if self.sample_clock >= CLOCK_RATE {
+                self.sample_clock -= CLOCK_RATE;
+                self.output_sample();
+            }
+        }
+    }
+
+    // Length at 256Hz, sweep at 128Hz and envelopes at 64Hz
+    fn step_sequencer(&mut self) {
+        if self.sequencer_step.is_multiple_of(2) {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.sequencer_step == 2 || self.sequencer_step == 6 {
+            self.square1.clock_sweep();
+        }
+        if self.sequencer_step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.sequencer_step = (self.sequencer_step + 1) % 8;
+    }
+
+    // Converts each channel through its DAC, pans them with NR51 and scales by the NR50 volumes
+    fn output_sample(&mut self) {
+        let outputs = [self.square1.output(), self.square2.output(), self.wave.output(), self.noise.output()];
+        let volumes = [((self.nr50 >> 4) & 0x07) + 1, (self.nr50 & 0x07) + 1];
+
+        let mut mixed = [0.0; 2];
+        for (channel, output) in outputs.into_iter().enumerate() {
+            let level = self.channel_level(channel, output, volumes);
+            mixed[0] += level[0];
+            mixed[1] += level[1];
+
+            if let Some(stems) = self.stems.as_mut() {
+                let sample = self.stem_filters[channel].filter(level, output.is_some(), self.charge_factor);
+                stems[channel].push(sample);
+            }
+        }
+
+        let any_dac = outputs.iter().any(Option::is_some);
+        let sample = self.filter.filter(mixed, any_dac, self.charge_factor);
+        self.samples.push(sample);
+    }
+
+    // One channel's contribution to the left and right outputs
+    fn channel_level(&self, channel: usize, output: Option<u8>, volumes: [u8; 2]) -> [f32; 2] {
+        let digital = match output {
+            Some(digital) => digital,
+            None => return [0.0; 2]
+        };
+
+        let analog = 1.0 - digital as f32 / 7.5;
+        let left = if self.nr51 & (0x10 << channel) != 0 { analog } else { 0.0 };
+        let right = if self.nr51 & (0x01 << channel) != 0 { analog } else { 0.0 };
+        [left / 4.0 * volumes[0] as f32 / 8.0, right / 4.0 * volumes[1] as f32 / 8.0]
+    }
+
+    fn power_off(&mut self) {
+        // Wave RAM survives, everything else is cleared
+        let wave_ram = self.wave.ram;
+        self.square1 = Square::new(true);
+        self.square2 = Square::new(false);
+        self.wave = Wave::new();
+        self.wave.ram = wave_ram;
+        self.noise = Noise::new();
+        self.nr50 = 0x00;
+        self.nr51 = 0x00;
+        self.powered = false;
+    }
+}
+
+// High-pass filter on the output, it removes the offset left by DACs that are on but silent
+struct HighPass {
+    capacitors: [f32; 2],
+}
+
+impl HighPass {
+    fn new() -> Self {
+        Self {
+            capacitors: [0.0; 2],
+        }
+    }
+
+    fn filter(&mut self, level: [f32; 2], dac_enabled: bool, charge_factor: f32) -> [i16; 2] {
+        let mut sample = [0; 2];
+        for (side, capacitor) in self.capacitors.iter_mut().enumerate() {
+            let filtered = if dac_enabled { level[side] - *capacitor } else { 0.0 };
+            *capacitor = level[side] - filtered * charge_factor;
+            sample[side] = (filtered * i16::MAX as f32) as i16;
+        }
+        sample
+    }
+}
+
+impl IoDevice for Apu {
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            NR10..=NR14 => self.square1.read(address - NR10),
+            0xFF15..=NR24 => self.square2.read(address - 0xFF15),
+            NR30..=NR34 => self.wave.read(address - NR30),
+            0xFF1F..=NR44 => self.noise.read(address - 0xFF1F),
+            NR50 => self.nr50,
+            NR51 => self.nr51,
+            NR52 => {
+                let channels = [self.square1.enabled, self.square2.enabled, self.wave.enabled, self.noise.enabled];
+                let status = channels.iter().rev().fold(0, |status, &enabled| (status << 1) | enabled as u8);
+                0x70 | if self.powered { 0x80 } else { 0x00 } | status
+            }
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave.read_ram(address - WAVE_RAM_START),
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        // Only NR52 and wave RAM can be written while the APU is off
+        if !self.powered && address != NR52 && !(WAVE_RAM_START..=WAVE_RAM_END).contains(&address) {
+            return;
+        }
+
+        match address {
+            NR10..=NR14 => self.square1.write(address - NR10, data),
+            0xFF15..=NR24 => self.square2.write(address - 0xFF15, data),
+            NR30..=NR34 => self.wave.write(address - NR30, data),
+            0xFF1F..=NR44 => self.noise.write(address - 0xFF1F, data),
+            NR50 => self.nr50 = data,
+            NR51 => self.nr51 = data,
+            NR52 => {
+                if data & 0x80 == 0 {
+                    self.power_off();
+                }
+                else if !self.powered {
+                    self.powered = true;
+                    self.sequencer_step = 0;
+                }
+            }
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave.write_ram(address - WAVE_RAM_START, data),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Powers the APU on and plays channel 2 at full volume
+    fn apu_for_test() -> Apu {
+        let mut apu = Apu::new();
+        apu.write(NR52, 0x80);
+        apu.write(NR50, 0x77);
+        apu.write(NR22, 0xF0);
+        apu.write(NR23, 0x00);
+        apu.write(NR24, 0x87);
+        apu
+    }
+
+    #[test]
+    fn register_reads() {
+        // Set up apu for test
+        let mut apu = apu_for_test();
+        apu.write(NR11, 0x80);
+        apu.write(NR30, 0x80);
+        apu.write(0xFF30, 0x12);
+
+        // Run test and compare output, write-only bits read back as 1
+        assert_eq!(apu.read(NR11), 0xBF);
+        assert_eq!(apu.read(NR13), 0xFF);
+        assert_eq!(apu.read(NR24), 0xBF);
+        assert_eq!(apu.read(NR30), 0xFF);
+        assert_eq!(apu.read(0xFF15), 0xFF);
+        assert_eq!(apu.read(0xFF27), 0xFF);
+        assert_eq!(apu.read(NR52), 0xF2);
+
+        // Powering off clears everything except wave RAM, and writes are ignored until it's back on
+        apu.write(NR52, 0x00);
+        assert_eq!(apu.read(NR52), 0x70);
+        assert_eq!(apu.read(NR11), 0x3F);
+        assert_eq!(apu.read(NR50), 0x00);
+        apu.write(NR50, 0x77);
+        assert_eq!(apu.read(NR50), 0x00);
+        assert_eq!(apu.read(0xFF30), 0x12);
+    }
+
+    // Takes DIV bit 4 high and back low, stepping the frame sequencer once
+    fn clock_sequencer(apu: &mut Apu) {
+        apu.tick(1, 0x0FFC);
+        apu.tick(1, 0x1FFC);
+    }
+
+    #[test]
+    fn length_runs_out() {
+        // Set up apu for test, a length of 2 is two 256Hz clocks
+        let mut apu = apu_for_test();
+        apu.write(NR21, 62);
+        apu.write(NR24, 0xC7);
+
+        // Run test and compare output, only even sequencer steps clock length
+        clock_sequencer(&mut apu);
+        assert_eq!(apu.read(NR52) & 0x02, 0x02);
+        clock_sequencer(&mut apu);
+        assert_eq!(apu.read(NR52) & 0x02, 0x02);
+        clock_sequencer(&mut apu);
+        assert_eq!(apu.read(NR52) & 0x02, 0x00);
+    }
+
+    #[test]
+    fn sample_rate() {
+        // Set up apu for test, one sample every 32 M-cycles
+        let mut apu = apu_for_test();
+        apu.set_sample_rate(32768);
+
+        // Run test and compare output
+        apu.tick(255, 0);
+        apu.tick(65, 0);
+        assert_eq!(apu.samples.len(), 10);
+    }
+
+    #[test]
+    fn panning() {
+        // Set up apu for test, channel 2 on the right only
+        let mut apu = apu_for_test();
+        apu.write(NR21, 0x80);
+        apu.write(NR51, 0x02);
+
+        // Run test and compare output
+        let mut samples = Vec::new();
+        for _ in 0..100 {
+            apu.tick(100, 0);
+            while let Some(sample) = apu.samples.pop() {
+                samples.push(sample);
+            }
+        }
+        assert_eq!(samples.iter().all(|sample| sample[0] == 0), true);
+        assert_eq!(samples.iter().any(|sample| sample[1] > 1000), true);
+        assert_eq!(samples.iter().any(|sample| sample[1] < -1000), true);
+    }
+
+    #[test]
+    fn stems() {
+        // Set up apu for test, channel 1 on the left and channel 2 on the right
+        let mut apu = apu_for_test();
+        apu.enable_stems();
+        apu.write(NR51, 0x12);
+        apu.write(NR12, 0xF0);
+        apu.write(NR14, 0x87);
+
+        // Run test and compare output, the stems add up to the mix
+        apu.tick(255, 0);
+        let stems = apu.stems.as_mut().unwrap();
+        let channel_1: Vec<[i16; 2]> = std::iter::from_fn(|| stems[0].pop()).collect();
+        let channel_2: Vec<[i16; 2]> = std::iter::from_fn(|| stems[1].pop()).collect();
+        assert_eq!(stems[2].len(), channel_1.len());
+
+        assert_eq!(channel_1.iter().all(|sample| sample[1] == 0), true);
+        assert_eq!(channel_2.iter().all(|sample| sample[0] == 0), true);
+        for (index, mixed) in std::iter::from_fn(|| apu.samples.pop()).enumerate() {
+            assert_eq!((mixed[0] - channel_1[index][0]).abs() <= 1, true);
+            assert_eq!((mixed[1] - channel_2[index][1]).abs() <= 1, true);
+        }
+    }
+}
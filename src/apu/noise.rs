@@ -0,0 +1,138 @@
+use super::channel::{Envelope, LengthCounter};
+
+// Bits of NR40 - NR44 that read back as 1, NR40 doesn't exist
+const READ_MASKS: [u8; 5] = [0xFF, 0xFF, 0x00, 0x00, 0xBF];
+
+// Base periods in T-cycles for the divisor code in NR43, shifted left by the clock shift
+const DIVISORS: [u16; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// Channel 4, outputs the low bit of a linear feedback shift register
+pub struct Noise {
+    pub enabled: bool,
+    // NR40 - NR44 as last written
+    registers: [u8; 5],
+    length: LengthCounter,
+    envelope: Envelope,
+    // T-cycles until the next shift
+    timer: u32,
+    lfsr: u16,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            registers: [0x00; 5],
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            timer: DIVISORS[0] as u32,
+            lfsr: 0x7FFF,
+        }
+    }
+
+    fn period(&self) -> u32 {
+        let nr43 = self.registers[3];
+        (DIVISORS[(nr43 & 0x07) as usize] as u32) << (nr43 >> 4)
+    }
+
+    pub fn read(&self, index: u16) -> u8 {
+        self.registers[index as usize] | READ_MASKS[index as usize]
+    }
+
+    pub fn write(&mut self, index: u16, data: u8) {
+        self.registers[index as usize] = data;
+        match index {
+            0 => {}
+            1 => self.length.load(data & 0x3F),
+            2 => {
+                self.envelope.register = data;
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => {}
+            _ => {
+                self.length.enabled = data & 0x40 != 0;
+                if data & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+    }
+
+    // Advances the shift register by a number of T-cycles
+    pub fn step(&mut self, cycles: u16) {
+        let mut cycles = cycles as u32;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.shift();
+        }
+        self.timer -= cycles;
+    }
+
+    fn shift(&mut self) {
+        let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+
+        // NR43 bit 3 also feeds bit 6, giving a short 7-bit sequence
+        if self.registers[3] & 0x08 != 0 {
+            self.lfsr = (self.lfsr & !0x40) | (feedback << 6);
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    // Digital output 0 - 15, None while the DAC is off
+    pub fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+
+        // The output is the inverted low bit
+        Some((!self.lfsr & 1) as u8 * self.envelope.volume)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_sequence_repeats() {
+        // Set up noise channel for test in 7-bit mode
+        let mut noise = Noise::new();
+        noise.write(2, 0xF0);
+        noise.write(3, 0x08);
+        noise.write(4, 0x80);
+
+        // Run test and compare output, the 7-bit LFSR repeats every 127 shifts
+        let mut outputs = Vec::new();
+        for _ in 0..254 {
+            noise.step(8);
+            outputs.push(noise.output());
+        }
+        assert_eq!(outputs[..127], outputs[127..]);
+        assert_eq!(outputs.contains(&Some(15)), true);
+        assert_eq!(outputs.contains(&Some(0)), true);
+    }
+}
@@ -0,0 +1,269 @@
+use super::channel::{Envelope, LengthCounter};
+
+// Waveforms selected by the duty bits in NRx1, read from bit 7 down
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+// Bits of NRx0 - NRx4 that read back as 1, NR20 doesn't exist
+const READ_MASKS: [u8; 5] = [0x80, 0x3F, 0x00, 0xFF, 0xBF];
+
+// Frequency sweep in NR10, only channel 1 has one
+pub struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    // Copy of the frequency the sweep works from
+    shadow: u16,
+    enabled: bool,
+    // Clearing negate after a subtraction has been done since the trigger turns the channel off
+    negate_used: bool,
+}
+
+impl Sweep {
+    fn new() -> Self {
+        Self {
+            period: 0,
+            negate: false,
+            shift: 0,
+            timer: 0,
+            shadow: 0,
+            enabled: false,
+            negate_used: false,
+        }
+    }
+
+    fn reload_timer(&mut self) {
+        // A period of 0 is treated as 8 by the timer
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    fn next_frequency(&mut self) -> u16 {
+        let delta = self.shadow >> self.shift;
+        if self.negate {
+            self.negate_used = true;
+            self.shadow - delta
+        }
+        else {
+            self.shadow + delta
+        }
+    }
+}
+
+// Square wave channels 1 and 2
+pub struct Square {
+    pub enabled: bool,
+    // NRx0 - NRx4 as last written
+    registers: [u8; 5],
+    length: LengthCounter,
+    envelope: Envelope,
+    sweep: Option<Sweep>,
+    frequency: u16,
+    // T-cycles until the next duty step
+    timer: u16,
+    duty_step: u8,
+}
+
+impl Square {
+    pub fn new(sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            registers: [0x00; 5],
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            sweep: sweep.then(Sweep::new),
+            frequency: 0,
+            timer: 2048 * 4,
+            duty_step: 0,
+        }
+    }
+
+    fn period(&self) -> u16 {
+        (2048 - self.frequency) * 4
+    }
+
+    pub fn read(&self, index: u16) -> u8 {
+        let mask = if index == 0 && self.sweep.is_none() { 0xFF } else { READ_MASKS[index as usize] };
+        self.registers[index as usize] | mask
+    }
+
+    pub fn write(&mut self, index: u16, data: u8) {
+        self.registers[index as usize] = data;
+        match index {
+            0 => {
+                if let Some(sweep) = self.sweep.as_mut() {
+                    sweep.period = (data >> 4) & 0x07;
+                    sweep.negate = data & 0x08 != 0;
+                    sweep.shift = data & 0x07;
+                    if !sweep.negate && sweep.negate_used {
+                        self.enabled = false;
+                    }
+                }
+            }
+            1 => self.length.load(data & 0x3F),
+            2 => {
+                self.envelope.register = data;
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | data as u16,
+            _ => {
+                self.frequency = (self.frequency & 0xFF) | ((data as u16 & 0x07) << 8);
+                self.length.enabled = data & 0x40 != 0;
+                if data & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = self.period();
+
+        if let Some(sweep) = self.sweep.as_mut() {
+            sweep.shadow = self.frequency;
+            sweep.reload_timer();
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+            sweep.negate_used = false;
+            // The overflow check runs straight away when there's a shift
+            if sweep.shift != 0 && sweep.next_frequency() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    // Advances the duty position by a number of T-cycles
+    pub fn step(&mut self, cycles: u16) {
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.duty_step = (self.duty_step + 1) & 0x07;
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let sweep = match self.sweep.as_mut() {
+            Some(sweep) => sweep,
+            None => return
+        };
+
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer != 0 {
+            return;
+        }
+        sweep.reload_timer();
+
+        if sweep.enabled && sweep.period != 0 {
+            let frequency = sweep.next_frequency();
+            if frequency > 2047 {
+                self.enabled = false;
+            }
+            else if sweep.shift != 0 {
+                sweep.shadow = frequency;
+                self.frequency = frequency;
+                // The new frequency is checked for overflow again but not used
+                if sweep.next_frequency() > 2047 {
+                    self.enabled = false;
+                }
+            }
+        }
+    }
+
+    // Digital output 0 - 15, None while the DAC is off
+    pub fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+
+        let duty = DUTY_PATTERNS[(self.registers[1] >> 6) as usize];
+        let high = (duty >> (7 - self.duty_step)) & 1;
+        Some(high * self.envelope.volume)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duty_cycle() {
+        // Set up square channel for test, 50% duty at the highest frequency
+        let mut square = Square::new(false);
+        square.write(1, 0x80);
+        square.write(2, 0xF0);
+        square.write(3, 0xFF);
+        square.write(4, 0x87);
+
+        // Run test and compare output, each step takes 4 T-cycles
+        let mut pattern = Vec::new();
+        for _ in 0..8 {
+            square.step(4);
+            pattern.push(square.output());
+        }
+        assert_eq!(pattern, [0, 0, 0, 0, 15, 15, 15, 15].map(Some));
+    }
+
+    #[test]
+    fn sweep_overflow() {
+        // Set up square channel for test, adding half the frequency every sweep clock
+        let mut square = Square::new(true);
+        square.write(0, 0x11);
+        square.write(2, 0xF0);
+        square.write(3, 0x00);
+        square.write(4, 0x84);
+
+        // Run test and compare output, 0x400 becomes 0x600 and the check after that overflows
+        assert_eq!(square.enabled, true);
+        square.clock_sweep();
+        assert_eq!(square.enabled, false);
+    }
+
+    #[test]
+    fn sweep_updates_frequency() {
+        // Set up square channel for test
+        let mut square = Square::new(true);
+        square.write(0, 0x22);
+        square.write(2, 0xF0);
+        square.write(3, 0x00);
+        square.write(4, 0x81);
+
+        // Run test and compare output, a quarter of the frequency is added every other clock
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x100);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x140);
+        assert_eq!(square.enabled, true);
+    }
+
+    #[test]
+    fn dac_off_disables() {
+        // Set up square channel for test
+        let mut square = Square::new(false);
+        square.write(2, 0xF0);
+        square.write(4, 0x80);
+
+        // Run test and compare output
+        assert_eq!(square.enabled, true);
+        square.write(2, 0x07);
+        assert_eq!(square.enabled, false);
+        assert_eq!(square.output(), None);
+    }
+}
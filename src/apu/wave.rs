@@ -0,0 +1,161 @@
+use super::channel::LengthCounter;
+
+// Bits of NR30 - NR34 that read back as 1
+const READ_MASKS: [u8; 5] = [0x7F, 0xFF, 0x9F, 0xFF, 0xBF];
+
+// Channel 3, plays back the 32 4-bit samples in wave RAM
+pub struct Wave {
+    pub enabled: bool,
+    // NR30 - NR34 as last written
+    registers: [u8; 5],
+    // 0xFF30 - 0xFF3F, two samples per byte with the high nibble first
+    pub ram: [u8; 0x10],
+    length: LengthCounter,
+    frequency: u16,
+    // T-cycles until the next sample
+    timer: u16,
+    position: u8,
+    sample: u8,
+}
+
+impl Wave {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            registers: [0x00; 5],
+            ram: [0x00; 0x10],
+            length: LengthCounter::new(256),
+            frequency: 0,
+            timer: 2048 * 2,
+            position: 0,
+            sample: 0,
+        }
+    }
+
+    fn period(&self) -> u16 {
+        (2048 - self.frequency) * 2
+    }
+
+    // NR30 bit 7 powers the DAC
+    fn dac_enabled(&self) -> bool {
+        self.registers[0] & 0x80 != 0
+    }
+
+    pub fn read(&self, index: u16) -> u8 {
+        self.registers[index as usize] | READ_MASKS[index as usize]
+    }
+
+    pub fn write(&mut self, index: u16, data: u8) {
+        self.registers[index as usize] = data;
+        match index {
+            0 => {
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(data),
+            2 => {}
+            3 => self.frequency = (self.frequency & 0x700) | data as u16,
+            _ => {
+                self.frequency = (self.frequency & 0xFF) | ((data as u16 & 0x07) << 8);
+                self.length.enabled = data & 0x40 != 0;
+                if data & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+        }
+    }
+
+    // While the channel plays, wave RAM accesses go to the byte it is reading from
+    pub fn read_ram(&self, offset: u16) -> u8 {
+        if self.enabled {
+            self.ram[(self.position / 2) as usize]
+        }
+        else {
+            self.ram[offset as usize]
+        }
+    }
+
+    pub fn write_ram(&mut self, offset: u16, data: u8) {
+        if self.enabled {
+            self.ram[(self.position / 2) as usize] = data;
+        }
+        else {
+            self.ram[offset as usize] = data;
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        self.length.trigger();
+        self.timer = self.period();
+        // The sample buffer isn't refilled, so the first sample played is the second one in RAM
+        self.position = 0;
+    }
+
+    // Advances the sample position by a number of T-cycles
+    pub fn step(&mut self, cycles: u16) {
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) & 0x1F;
+
+            let byte = self.ram[(self.position / 2) as usize];
+            self.sample = if self.position.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    // Digital output 0 - 15, None while the DAC is off
+    pub fn output(&self) -> Option<u8> {
+        if !self.dac_enabled() {
+            return None;
+        }
+        if !self.enabled {
+            return Some(0);
+        }
+
+        // Output level in NR32: mute, 100%, 50% or 25%
+        let shift = match (self.registers[2] >> 5) & 0x03 {
+            0 => 4,
+            code => code - 1
+        };
+        Some(self.sample >> shift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_wave_ram() {
+        // Set up wave channel for test at full volume
+        let mut wave = Wave::new();
+        wave.ram[0] = 0x8F;
+        wave.ram[1] = 0x4C;
+        wave.write(0, 0x80);
+        wave.write(2, 0x20);
+        wave.write(3, 0xFF);
+        wave.write(4, 0x87);
+
+        // Run test and compare output, the first sample is skipped
+        let mut samples = Vec::new();
+        for _ in 0..3 {
+            wave.step(2);
+            samples.push(wave.output());
+        }
+        assert_eq!(samples, [Some(15), Some(4), Some(12)]);
+
+        // A quarter of the volume shifts the sample right twice
+        wave.write(2, 0x60);
+        assert_eq!(wave.output(), Some(3));
+    }
+}
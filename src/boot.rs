@@ -0,0 +1,287 @@
+use crate::apu::{
+    NR10, NR11, NR12, NR13, NR14, NR21, NR22, NR23, NR24, NR30, NR31, NR32, NR33, NR34, NR41, NR42, NR43,
+    NR44, NR50, NR51, NR52,
+};
+use crate::cpu::{Flag, RegisterU16};
+use crate::gameboy::Gameboy;
+use crate::interrupt::INTERRUPT_FLAG;
+use crate::joypad::P1;
+use crate::ppu::{BGP, LCDC, STAT, WY};
+use crate::serial::{SB, SC};
+use crate::timer::{TAC, TIMA, TMA};
+
+// Writing to this register unmaps the boot ROM until the next power cycle
+pub const BOOT_ROM_DISABLE: u16 = 0xFF50;
+
+// Hardware revision being emulated, which decides the state the boot ROM hands over in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Model {
+    Dmg,
+    // Game Boy Pocket
+    Mgb,
+    Sgb,
+    Cgb
+}
+
+// Nintendo logo location in the cartridge header, drawn into VRAM by the boot ROM
+const LOGO_START: u16 = 0x104;
+const LOGO_SIZE: u16 = 0x30;
+
+// Registered trademark symbol the DMG boot ROM draws after the logo
+const TRADEMARK_TILE: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
+// I/O registers left behind by the DMG boot ROM, the other models differ in a few entries below.
+// NR52 comes first since the APU ignores writes to its other registers while it's off. The sound
+// registers' write-only bits hold what the boot ROM last wrote rather than how they read back,
+// and NRx4 goes in without its trigger bit so no channel starts playing.
+const DMG_IO_REGISTERS: [(u16, u8); 31] = [
+    (NR52, 0x80), (P1, 0xCF), (SB, 0x00), (SC, 0x7E), (TIMA, 0x00), (TMA, 0x00), (TAC, 0xF8),
+    (NR10, 0x80), (NR11, 0x80), (NR12, 0xF3), (NR13, 0xC1), (NR14, 0x07),
+    (NR21, 0x00), (NR22, 0x00), (NR23, 0x00), (NR24, 0x00),
+    (NR30, 0x00), (NR31, 0x00), (NR32, 0x00), (NR33, 0x00), (NR34, 0x00),
+    (NR41, 0x00), (NR42, 0x00), (NR43, 0x00), (NR44, 0x00),
+    (NR50, 0x77), (NR51, 0xF3), (LCDC, 0x91), (STAT, 0x85), (BGP, 0xFC), (WY, 0x00),
+];
+
+impl Gameboy {
+    // Maps a boot ROM over the start of the cartridge and runs it from address 0
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.memory.boot_rom = Some(boot_rom);
+        self.cpu.register.pc = 0x0000;
+    }
+
+    // Puts the hardware in the state the boot ROM would have left it in for the current model,
+    // as documented in Pan Docs. Call after the cartridge is loaded since some values depend on it.
+    pub fn skip_boot(&mut self) {
+        let header_checksum = self.read_instruction(0x014D);
+        let cgb_cartridge = self.read_instruction(0x0143) & 0x80 != 0;
+
+        // (A, F, B, C, D, E, H, L)
+        let registers: [u8; 8] = match self.model {
+            // The half carry and carry flags are left over from the header checksum loop
+            Model::Dmg if header_checksum == 0 => [0x01, 0x80, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Dmg => [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Mgb if header_checksum == 0 => [0xFF, 0x80, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Mgb => [0xFF, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Sgb => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::Cgb if cgb_cartridge => [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+            // B is really a hash of the title, used to pick a compatibility palette
+            Model::Cgb => [0x11, 0x80, 0x00, 0x00, 0x00, 0x08, 0x00, 0x7C],
+        };
+        let [a, f, b, c, d, e, h, l] = registers;
+        self.cpu.register.write_u16(RegisterU16::AF, u16::from_be_bytes([a, f]));
+        self.cpu.register.write_u16(RegisterU16::BC, u16::from_be_bytes([b, c]));
+        self.cpu.register.write_u16(RegisterU16::DE, u16::from_be_bytes([d, e]));
+        self.cpu.register.write_u16(RegisterU16::HL, u16::from_be_bytes([h, l]));
+        self.cpu.flags.set_flag(Flag::Z, f & 0x80 != 0);
+        self.cpu.flags.set_flag(Flag::N, f & 0x40 != 0);
+        self.cpu.flags.set_flag(Flag::H, f & 0x20 != 0);
+        self.cpu.flags.set_flag(Flag::C, f & 0x10 != 0);
+        self.cpu.register.sp = 0xFFFE;
+        self.cpu.register.pc = 0x0100;
+
+        for (address, data) in DMG_IO_REGISTERS {
+            self.write_instruction(address, data);
+        }
+        self.write_instruction(INTERRUPT_FLAG, 0xE1);
+        // Set directly since writing FF46 would start a transfer
+        self.memory.dma.register = 0xFF;
+        if self.model == Model::Cgb {
+            self.write_instruction(0xFF02, 0x7F);
+            self.memory.dma.register = 0x00;
+            self.write_instruction(0xFF4F, 0xFE);
+        }
+
+        // The SGB boot ROM plays no chime, so channel 1 stays off
+        if self.model != Model::Sgb {
+            self.memory.apu.finish_boot_chime();
+        }
+
+        // DIV has been counting since power on, the CGB value depends on how long the logo took
+        if matches!(self.model, Model::Dmg | Model::Mgb) {
+            self.memory.timer.counter = 0xABCC;
+        }
+
+        // The CGB boot ROM lays out its logo differently and clears VRAM when it hands over
+        if self.model != Model::Cgb {
+            self.draw_boot_logo();
+        }
+
+        self.memory.boot_rom = None;
+    }
+
+    // Decompresses the cartridge's logo into tiles 1 - 24 and places it in the background map the
+    // way the DMG boot ROM does, each pixel doubled in both directions
+    fn draw_boot_logo(&mut self) {
+        let mut tile_address = 0x8010;
+        for offset in 0..LOGO_SIZE {
+            let logo_byte = self.read_instruction(LOGO_START + offset);
+            for nibble in [logo_byte >> 4, logo_byte & 0x0F] {
+                let mut row = 0;
+                for bit in (0..4).rev() {
+                    let pixel = (nibble >> bit) & 1;
+                    row = (row << 2) | (pixel << 1) | pixel;
+                }
+                // Only the low bitplane is written, so every other byte
+                self.write_instruction(tile_address, row);
+                self.write_instruction(tile_address + 2, row);
+                tile_address += 4;
+            }
+        }
+
+        for (i, row) in TRADEMARK_TILE.iter().enumerate() {
+            self.write_instruction(0x8190 + i as u16 * 2, *row);
+        }
+
+        for tile in 0..12 {
+            self.write_instruction(0x9904 + tile, tile as u8 + 1);
+            self.write_instruction(0x9924 + tile, tile as u8 + 13);
+        }
+        self.write_instruction(0x9910, 0x19);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::tests::build_rom;
+    use crate::cartridge::Cartridge;
+    use crate::cpu::RegisterU8;
+
+    // Start of the Nintendo logo as it appears in every licensed header
+    const LOGO: [u8; 4] = [0xCE, 0xED, 0x66, 0x66];
+
+    fn gameboy_with_cartridge(model: Model) -> Gameboy {
+        let mut rom = build_rom(0x8000, 0x00, 0x00, 0x00);
+        rom[0x104..0x108].copy_from_slice(&LOGO);
+        rom[0x14D] = crate::cartridge::compute_header_checksum(&rom);
+        let mut gameboy = Gameboy::new();
+        gameboy.set_model(model);
+        gameboy.memory.load_cartridge(Cartridge::from_bytes(rom).unwrap());
+        gameboy
+    }
+
+    #[test]
+    fn boot_rom_overlay() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = gameboy_with_cartridge(Model::Dmg);
+
+        // Set up gameboy state for test
+        gameboy.load_boot_rom(vec![0x31; 0x100]);
+
+        // Run test and compare output
+        assert_eq!(gameboy.read_instruction(0x0000), 0x31);
+        assert_eq!(gameboy.read_instruction(0x00FF), 0x31);
+        assert_eq!(gameboy.read_instruction(0x0104), 0xCE);
+
+        gameboy.write_instruction(BOOT_ROM_DISABLE, 0x01);
+        assert_eq!(gameboy.read_instruction(0x0000), 0x00);
+
+        // Once unmapped it stays unmapped
+        gameboy.write_instruction(BOOT_ROM_DISABLE, 0x00);
+        assert_eq!(gameboy.read_instruction(0x0000), 0x00);
+    }
+
+    #[test]
+    fn cgb_boot_rom_skips_header() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = gameboy_with_cartridge(Model::Cgb);
+
+        // Set up gameboy state for test
+        gameboy.load_boot_rom(vec![0x31; 0x900]);
+
+        // Run test and compare output
+        assert_eq!(gameboy.read_instruction(0x0104), 0xCE);
+        assert_eq!(gameboy.read_instruction(0x0200), 0x31);
+        assert_eq!(gameboy.read_instruction(0x08FF), 0x31);
+    }
+
+    #[test]
+    fn skip_boot_dmg() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = gameboy_with_cartridge(Model::Dmg);
+
+        // Run test and compare output
+        gameboy.skip_boot();
+        assert_eq!(gameboy.cpu.register.read_u16(RegisterU16::AF), 0x01B0);
+        assert_eq!(gameboy.cpu.register.read_u16(RegisterU16::BC), 0x0013);
+        assert_eq!(gameboy.cpu.register.read_u16(RegisterU16::DE), 0x00D8);
+        assert_eq!(gameboy.cpu.register.read_u16(RegisterU16::HL), 0x014D);
+        assert_eq!(gameboy.cpu.flags.get_flag(Flag::Z), true);
+        assert_eq!(gameboy.cpu.flags.get_flag(Flag::C), true);
+        assert_eq!(gameboy.cpu.register.sp, 0xFFFE);
+        assert_eq!(gameboy.cpu.register.pc, 0x0100);
+        assert_eq!(gameboy.read_instruction(0xFF04), 0xAB);
+        assert_eq!(gameboy.read_instruction(0xFF40), 0x91);
+        assert_eq!(gameboy.read_instruction(0xFF47), 0xFC);
+        assert_eq!(gameboy.read_instruction(INTERRUPT_FLAG), 0xE1);
+    }
+
+    #[test]
+    fn skip_boot_models() {
+        // Run test and compare output
+        let mut mgb = gameboy_with_cartridge(Model::Mgb);
+        mgb.skip_boot();
+        assert_eq!(mgb.cpu.register.read_u8(RegisterU8::A), 0xFF);
+
+        let mut sgb = gameboy_with_cartridge(Model::Sgb);
+        sgb.skip_boot();
+        assert_eq!(sgb.cpu.register.read_u16(RegisterU16::AF), 0x0100);
+        assert_eq!(sgb.cpu.register.read_u16(RegisterU16::HL), 0xC060);
+        assert_eq!(sgb.cpu.flags.get_flag(Flag::Z), false);
+
+        let mut cgb = gameboy_with_cartridge(Model::Cgb);
+        cgb.skip_boot();
+        assert_eq!(cgb.cpu.register.read_u16(RegisterU16::AF), 0x1180);
+        assert_eq!(cgb.cpu.register.read_u16(RegisterU16::HL), 0x007C);
+        assert_eq!(cgb.read_instruction(0x8010), 0x00);
+    }
+
+    #[test]
+    fn skip_boot_sound_state() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = gameboy_with_cartridge(Model::Dmg);
+        gameboy.skip_boot();
+
+        // Run test and compare output, registers read back as documented
+        let reads: Vec<u8> = (0xFF10..=0xFF26).map(|address| gameboy.read_instruction(address)).collect();
+        assert_eq!(reads, [
+            0x80, 0xBF, 0xF3, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+            0xFF, 0xFF, 0x00, 0x00, 0xBF, 0x77, 0xF3, 0xF1
+        ]);
+
+        // Channel 1 is on but nothing is playing, so the output only settles towards zero
+        for _ in 0..10000 {
+            gameboy.fetch();
+        }
+        let mut samples = Vec::new();
+        while let Some(sample) = gameboy.memory.apu.samples.pop() {
+            samples.push(sample[0]);
+        }
+        assert_eq!(samples.is_empty(), false);
+        assert_eq!(samples.windows(2).all(|pair| pair[0].abs_diff(pair[1]) < 64), true);
+
+        let mut sgb = gameboy_with_cartridge(Model::Sgb);
+        sgb.skip_boot();
+        assert_eq!(sgb.read_instruction(0xFF26), 0xF0);
+    }
+
+    #[test]
+    fn skip_boot_draws_logo() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = gameboy_with_cartridge(Model::Dmg);
+
+        // Run test and compare output
+        gameboy.skip_boot();
+
+        // 0xCE, the high nibble 1100 doubles to 11110000 and the low nibble 1110 to 11111100
+        assert_eq!(gameboy.read_instruction(0x8010), 0xF0);
+        assert_eq!(gameboy.read_instruction(0x8012), 0xF0);
+        assert_eq!(gameboy.read_instruction(0x8014), 0xFC);
+        assert_eq!(gameboy.read_instruction(0x8011), 0x00);
+        assert_eq!(gameboy.read_instruction(0x8190), 0x3C);
+        assert_eq!(gameboy.read_instruction(0x9904), 0x01);
+        assert_eq!(gameboy.read_instruction(0x992F), 0x18);
+        assert_eq!(gameboy.read_instruction(0x9910), 0x19);
+    }
+}
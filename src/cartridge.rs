@@ -0,0 +1,710 @@
+use std::fmt;
+
+use crate::mbc::rtc::{Clock, Rtc, SystemClock};
+use crate::mbc::{Mbc, Mbc1, Mbc2, Mbc3, Mbc5, RomOnly, MBC2_RAM_SIZE};
+
+// Header layout, 0x0100 - 0x014F
+const HEADER_END: usize = 0x150;
+const TITLE_START: usize = 0x134;
+const TITLE_END: usize = 0x144;
+const CGB_FLAG: usize = 0x143;
+const NEW_LICENSEE_CODE: usize = 0x144;
+const SGB_FLAG: usize = 0x146;
+const CARTRIDGE_TYPE: usize = 0x147;
+const ROM_SIZE: usize = 0x148;
+const RAM_SIZE: usize = 0x149;
+const OLD_LICENSEE_CODE: usize = 0x14B;
+const VERSION: usize = 0x14C;
+const HEADER_CHECKSUM: usize = 0x14D;
+const GLOBAL_CHECKSUM: usize = 0x14E;
+
+// Largest ROM any licensed mapper can address, 512 banks of 16 KiB
+const MAX_ROM_SIZE: usize = 0x80_0000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CartridgeError {
+    // The file ends before the header or before the ROM size the header declares
+    Truncated { expected: usize, actual: usize },
+    // The file is larger than the ROM size the header declares
+    Oversized { expected: usize, actual: usize },
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    // The boot ROM refuses to start a cartridge whose header checksum does not match
+    HeaderChecksum { expected: u8, computed: u8 },
+    // The header names a memory bank controller the emulator doesn't implement yet
+    UnsupportedMapper(Mapper),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Truncated { expected, actual } => {
+                write!(f, "ROM is truncated, expected {} bytes but found {}", expected, actual)
+            }
+            CartridgeError::Oversized { expected, actual } => {
+                write!(f, "ROM is oversized, expected {} bytes but found {}", expected, actual)
+            }
+            CartridgeError::UnknownCartridgeType(code) => write!(f, "unknown cartridge type {:#04X}", code),
+            CartridgeError::UnknownRomSize(code) => write!(f, "unknown ROM size code {:#04X}", code),
+            CartridgeError::UnknownRamSize(code) => write!(f, "unknown RAM size code {:#04X}", code),
+            CartridgeError::HeaderChecksum { expected, computed } => {
+                write!(f, "header checksum mismatch, header says {:#04X} but computed {:#04X}", expected, computed)
+            }
+            CartridgeError::UnsupportedMapper(mapper) => write!(f, "{} cartridges are not supported", mapper),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+// Memory bank controller fitted to the cartridge
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    Mmm01,
+    PocketCamera,
+    Tama5,
+    HuC1,
+    HuC3
+}
+
+impl fmt::Display for Mapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Mapper::RomOnly => "ROM",
+            Mapper::Mbc1 => "MBC1",
+            Mapper::Mbc2 => "MBC2",
+            Mapper::Mbc3 => "MBC3",
+            Mapper::Mbc5 => "MBC5",
+            Mapper::Mbc6 => "MBC6",
+            Mapper::Mbc7 => "MBC7",
+            Mapper::Mmm01 => "MMM01",
+            Mapper::PocketCamera => "POCKET CAMERA",
+            Mapper::Tama5 => "TAMA5",
+            Mapper::HuC1 => "HuC1",
+            Mapper::HuC3 => "HuC3"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Decoded cartridge type byte at 0x0147
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mapper: Mapper,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Option<CartridgeType> {
+        // (mapper, ram, battery, timer, rumble)
+        let (mapper, ram, battery, timer, rumble) = match code {
+            0x00 => (Mapper::RomOnly, false, false, false, false),
+            0x01 => (Mapper::Mbc1, false, false, false, false),
+            0x02 => (Mapper::Mbc1, true, false, false, false),
+            0x03 => (Mapper::Mbc1, true, true, false, false),
+            // MBC2 has its RAM built in, so the header never reports any
+            0x05 => (Mapper::Mbc2, false, false, false, false),
+            0x06 => (Mapper::Mbc2, false, true, false, false),
+            0x08 => (Mapper::RomOnly, true, false, false, false),
+            0x09 => (Mapper::RomOnly, true, true, false, false),
+            0x0B => (Mapper::Mmm01, false, false, false, false),
+            0x0C => (Mapper::Mmm01, true, false, false, false),
+            0x0D => (Mapper::Mmm01, true, true, false, false),
+            0x0F => (Mapper::Mbc3, false, true, true, false),
+            0x10 => (Mapper::Mbc3, true, true, true, false),
+            0x11 => (Mapper::Mbc3, false, false, false, false),
+            0x12 => (Mapper::Mbc3, true, false, false, false),
+            0x13 => (Mapper::Mbc3, true, true, false, false),
+            0x19 => (Mapper::Mbc5, false, false, false, false),
+            0x1A => (Mapper::Mbc5, true, false, false, false),
+            0x1B => (Mapper::Mbc5, true, true, false, false),
+            0x1C => (Mapper::Mbc5, false, false, false, true),
+            0x1D => (Mapper::Mbc5, true, false, false, true),
+            0x1E => (Mapper::Mbc5, true, true, false, true),
+            0x20 => (Mapper::Mbc6, false, false, false, false),
+            0x22 => (Mapper::Mbc7, true, true, false, true),
+            0xFC => (Mapper::PocketCamera, false, false, false, false),
+            0xFD => (Mapper::Tama5, false, false, false, false),
+            0xFE => (Mapper::HuC3, false, false, false, false),
+            0xFF => (Mapper::HuC1, true, true, false, false),
+            _ => return None
+        };
+
+        Some(CartridgeType { code, mapper, ram, battery, timer, rumble })
+    }
+}
+
+impl fmt::Display for CartridgeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mapper)?;
+        if self.timer {
+            write!(f, "+TIMER")?;
+        }
+        if self.rumble {
+            write!(f, "+RUMBLE")?;
+        }
+        if self.ram {
+            write!(f, "+RAM")?;
+        }
+        if self.battery {
+            write!(f, "+BATTERY")?;
+        }
+        write!(f, " ({:#04X})", self.code)
+    }
+}
+
+// Value of the CGB flag at 0x0143
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CgbSupport {
+    None,
+    // Runs on DMG and takes advantage of CGB features when available
+    Enhanced,
+    Only
+}
+
+impl fmt::Display for CgbSupport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CgbSupport::None => "no",
+            CgbSupport::Enhanced => "enhanced",
+            CgbSupport::Only => "required"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Publisher code, later cartridges store two ASCII characters at 0x0144 and set 0x014B to 0x33
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Licensee {
+    Old(u8),
+    New([u8; 2])
+}
+
+impl fmt::Display for Licensee {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Licensee::Old(code) => write!(f, "{:#04X}", code),
+            Licensee::New(code) => write!(f, "\"{}{}\"", code[0] as char, code[1] as char)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub cgb_support: CgbSupport,
+    pub sgb_support: bool,
+    pub cartridge_type: CartridgeType,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    pub licensee: Licensee,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, CartridgeError> {
+        if rom.len() < HEADER_END {
+            return Err(CartridgeError::Truncated { expected: HEADER_END, actual: rom.len() });
+        }
+
+        let cgb_support = match rom[CGB_FLAG] {
+            0xC0 => CgbSupport::Only,
+            0x80 => CgbSupport::Enhanced,
+            _ => CgbSupport::None
+        };
+
+        // The last title byte doubles as the CGB flag on colour aware cartridges
+        let title_end = match cgb_support {
+            CgbSupport::None => TITLE_END,
+            _ => CGB_FLAG
+        };
+        let title = rom[TITLE_START..title_end]
+            .iter()
+            .take_while(|&&byte| byte != 0x00)
+            .map(|&byte| byte as char)
+            .collect::<String>();
+
+        let cartridge_type = CartridgeType::from_code(rom[CARTRIDGE_TYPE])
+            .ok_or(CartridgeError::UnknownCartridgeType(rom[CARTRIDGE_TYPE]))?;
+
+        let rom_size = match rom[ROM_SIZE] {
+            code @ 0x00..=0x08 => 0x8000 << code,
+            code => return Err(CartridgeError::UnknownRomSize(code))
+        };
+
+        let ram_size = match rom[RAM_SIZE] {
+            0x00 => 0,
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            code => return Err(CartridgeError::UnknownRamSize(code))
+        };
+
+        let licensee = match rom[OLD_LICENSEE_CODE] {
+            0x33 => Licensee::New([rom[NEW_LICENSEE_CODE], rom[NEW_LICENSEE_CODE + 1]]),
+            code => Licensee::Old(code)
+        };
+
+        Ok(CartridgeHeader {
+            title,
+            cgb_support,
+            sgb_support: rom[SGB_FLAG] == 0x03,
+            cartridge_type,
+            rom_size,
+            ram_size,
+            licensee,
+            version: rom[VERSION],
+            header_checksum: rom[HEADER_CHECKSUM],
+            global_checksum: u16::from_be_bytes([rom[GLOBAL_CHECKSUM], rom[GLOBAL_CHECKSUM + 1]]),
+        })
+    }
+}
+
+impl fmt::Display for CartridgeHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Title:           {}", self.title)?;
+        writeln!(f, "Type:            {}", self.cartridge_type)?;
+        writeln!(f, "ROM size:        {} KiB", self.rom_size / 1024)?;
+        writeln!(f, "RAM size:        {} KiB", self.ram_size / 1024)?;
+        writeln!(f, "CGB:             {}", self.cgb_support)?;
+        writeln!(f, "SGB:             {}", if self.sgb_support { "yes" } else { "no" })?;
+        writeln!(f, "Licensee:        {}", self.licensee)?;
+        writeln!(f, "Version:         {}", self.version)?;
+        writeln!(f, "Header checksum: {:#04X}", self.header_checksum)?;
+        write!(f, "Global checksum: {:#06X}", self.global_checksum)
+    }
+}
+
+// Sum used by the boot ROM to validate 0x0134 - 0x014C
+pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+    rom[TITLE_START..HEADER_CHECKSUM]
+        .iter()
+        .fold(0u8, |checksum, &byte| checksum.wrapping_sub(byte).wrapping_sub(1))
+}
+
+// Sum of every byte in the ROM except the two checksum bytes themselves
+pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(address, _)| *address != GLOBAL_CHECKSUM && *address != GLOBAL_CHECKSUM + 1)
+        .fold(0u16, |checksum, (_, &byte)| checksum.wrapping_add(byte as u16))
+}
+
+pub struct Cartridge {
+    pub header: CartridgeHeader,
+    rom: Vec<u8>,
+    pub ram: Vec<u8>,
+    mbc: Box<dyn Mbc>,
+    // RAM has been written since the save file was last flushed
+    pub ram_dirty: bool,
+    // The game disabled RAM after writing to it, which is when it considers a save complete
+    pub flush_requested: bool,
+}
+
+impl Cartridge {
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        Cartridge::from_bytes_with_clock(rom, Box::new(SystemClock))
+    }
+
+    // The clock drives the MBC3 RTC, tests pass in one they can move forward by hand
+    pub fn from_bytes_with_clock(rom: Vec<u8>, clock: Box<dyn Clock>) -> Result<Cartridge, CartridgeError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(CartridgeError::Oversized { expected: MAX_ROM_SIZE, actual: rom.len() });
+        }
+
+        let header = CartridgeHeader::parse(&rom)?;
+
+        let computed = compute_header_checksum(&rom);
+        if computed != header.header_checksum {
+            return Err(CartridgeError::HeaderChecksum { expected: header.header_checksum, computed });
+        }
+
+        if rom.len() < header.rom_size {
+            return Err(CartridgeError::Truncated { expected: header.rom_size, actual: rom.len() });
+        }
+        if rom.len() > header.rom_size {
+            return Err(CartridgeError::Oversized { expected: header.rom_size, actual: rom.len() });
+        }
+
+        let mbc: Box<dyn Mbc> = match header.cartridge_type.mapper {
+            Mapper::RomOnly => Box::new(RomOnly),
+            Mapper::Mbc1 => Box::new(Mbc1::new(Mbc1::is_multicart(&rom))),
+            Mapper::Mbc2 => Box::new(Mbc2::new()),
+            Mapper::Mbc3 => {
+                let rtc = header.cartridge_type.timer.then(|| Rtc::new(clock));
+                Box::new(Mbc3::new(rtc))
+            }
+            Mapper::Mbc5 => Box::new(Mbc5::new(header.cartridge_type.rumble)),
+            mapper => return Err(CartridgeError::UnsupportedMapper(mapper))
+        };
+
+        // MBC2 carries its own RAM which the header doesn't count
+        let ram_size = match header.cartridge_type.mapper {
+            Mapper::Mbc2 => MBC2_RAM_SIZE,
+            _ => header.ram_size
+        };
+        let ram = vec![0x00; ram_size];
+
+        Ok(Cartridge { header, rom, ram, mbc, ram_dirty: false, flush_requested: false })
+    }
+
+    // Real hardware never checks this, so a mismatch is reported rather than rejected
+    pub fn global_checksum_valid(&self) -> bool {
+        compute_global_checksum(&self.rom) == self.header.global_checksum
+    }
+
+    // 0x0000 - 0x7FFF
+    pub fn read_rom(&self, address: u16) -> u8 {
+        self.mbc.read_rom(&self.rom, address)
+    }
+
+    pub fn write_rom(&mut self, address: u16, data: u8) {
+        let was_enabled = self.mbc.ram_enabled();
+        self.mbc.write_rom(address, data);
+        // RTC games enable and disable RAM every frame just to read the clock, only a write
+        // (RTC register writes included) makes the save worth flushing
+        if was_enabled && !self.mbc.ram_enabled() && self.ram_dirty {
+            self.flush_requested = true;
+        }
+    }
+
+    // 0xA000 - 0xBFFF, open bus reads back as 0xFF when no RAM is fitted
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.mbc.read_ram(&self.ram, address)
+    }
+
+    pub fn write_ram(&mut self, address: u16, data: u8) {
+        if self.mbc.ram_enabled() {
+            self.ram_dirty = true;
+        }
+        self.mbc.write_ram(&mut self.ram, address, data);
+    }
+
+    // Whether the cartridge keeps its RAM or clock powered and needs a save file
+    pub fn has_battery(&self) -> bool {
+        self.header.cartridge_type.battery
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+
+    // Contents of a save file, the RAM followed by any mapper state such as the RTC footer
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(footer) = self.mbc.save_footer() {
+            data.extend(footer);
+        }
+        data
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let ram_size = self.ram.len().min(data.len());
+        self.ram[..ram_size].copy_from_slice(&data[..ram_size]);
+        if data.len() > self.ram.len() {
+            self.mbc.load_save_footer(&data[self.ram.len()..]);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::gameboy::Gameboy;
+    use crate::mbc::rtc::tests::FakeClock;
+
+    // Builds a ROM of the given size with a valid header for the given type, ROM and RAM size codes
+    pub fn build_rom(size: usize, cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0x00; size];
+        rom[TITLE_START..TITLE_START + 6].copy_from_slice(b"TETRIS");
+        rom[CARTRIDGE_TYPE] = cartridge_type;
+        rom[ROM_SIZE] = rom_size;
+        rom[RAM_SIZE] = ram_size;
+        rom[OLD_LICENSEE_CODE] = 0x01;
+        rom[VERSION] = 0x01;
+        rom[HEADER_CHECKSUM] = compute_header_checksum(&rom);
+        let global = compute_global_checksum(&rom).to_be_bytes();
+        rom[GLOBAL_CHECKSUM] = global[0];
+        rom[GLOBAL_CHECKSUM + 1] = global[1];
+        rom
+    }
+
+    #[test]
+    fn parse_header() {
+        // Set up cartridge for test
+        let rom = build_rom(0x10000, 0x03, 0x01, 0x02);
+
+        // Run test and compare output
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        let header = &cartridge.header;
+        assert_eq!(header.title, "TETRIS");
+        assert_eq!(header.cartridge_type.mapper, Mapper::Mbc1);
+        assert_eq!(header.cartridge_type.ram, true);
+        assert_eq!(header.cartridge_type.battery, true);
+        assert_eq!(header.rom_size, 0x10000);
+        assert_eq!(header.ram_size, 0x2000);
+        assert_eq!(header.cgb_support, CgbSupport::None);
+        assert_eq!(header.sgb_support, false);
+        assert_eq!(header.licensee, Licensee::Old(0x01));
+        assert_eq!(header.version, 0x01);
+        assert_eq!(cartridge.ram.len(), 0x2000);
+        assert_eq!(cartridge.global_checksum_valid(), true);
+    }
+
+    #[test]
+    fn parse_cgb_title_and_new_licensee() {
+        // Set up cartridge for test
+        let mut rom = build_rom(0x8000, 0x00, 0x00, 0x00);
+        rom[TITLE_START..TITLE_END].copy_from_slice(b"POKEMON_SLVAAXE\x80");
+        rom[NEW_LICENSEE_CODE] = b'0';
+        rom[NEW_LICENSEE_CODE + 1] = b'1';
+        rom[OLD_LICENSEE_CODE] = 0x33;
+        rom[SGB_FLAG] = 0x03;
+
+        // Run test and compare output
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "POKEMON_SLVAAXE");
+        assert_eq!(header.cgb_support, CgbSupport::Enhanced);
+        assert_eq!(header.sgb_support, true);
+        assert_eq!(header.licensee, Licensee::New([b'0', b'1']));
+    }
+
+    #[test]
+    fn truncated_header() {
+        // Run test and compare output
+        let result = Cartridge::from_bytes(vec![0x00; 0x100]);
+        assert_eq!(result.err(), Some(CartridgeError::Truncated { expected: 0x150, actual: 0x100 }));
+    }
+
+    #[test]
+    fn truncated_rom() {
+        // Set up cartridge for test, header claims 64 KiB
+        let mut rom = build_rom(0x10000, 0x01, 0x01, 0x00);
+        rom.truncate(0x8000);
+
+        // Run test and compare output
+        let result = Cartridge::from_bytes(rom);
+        assert_eq!(result.err(), Some(CartridgeError::Truncated { expected: 0x10000, actual: 0x8000 }));
+    }
+
+    #[test]
+    fn oversized_rom() {
+        // Set up cartridge for test, header claims 32 KiB
+        let mut rom = build_rom(0x8000, 0x00, 0x00, 0x00);
+        rom.resize(0x10000, 0x00);
+
+        // Run test and compare output
+        let result = Cartridge::from_bytes(rom);
+        assert_eq!(result.err(), Some(CartridgeError::Oversized { expected: 0x8000, actual: 0x10000 }));
+    }
+
+    #[test]
+    fn header_checksum_mismatch() {
+        // Set up cartridge for test
+        let mut rom = build_rom(0x8000, 0x00, 0x00, 0x00);
+        let computed = rom[HEADER_CHECKSUM];
+        rom[HEADER_CHECKSUM] = computed.wrapping_add(1);
+
+        // Run test and compare output
+        let result = Cartridge::from_bytes(rom);
+        assert_eq!(result.err(), Some(CartridgeError::HeaderChecksum { expected: computed.wrapping_add(1), computed }));
+    }
+
+    #[test]
+    fn global_checksum_mismatch() {
+        // Set up cartridge for test
+        let mut rom = build_rom(0x8000, 0x00, 0x00, 0x00);
+        rom[0x4000] = 0xFF;
+
+        // Run test and compare output
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        assert_eq!(cartridge.global_checksum_valid(), false);
+    }
+
+    #[test]
+    fn unknown_codes() {
+        // Set up cartridge for test
+        let mut rom = build_rom(0x8000, 0x00, 0x00, 0x00);
+
+        // Run test and compare output
+        rom[CARTRIDGE_TYPE] = 0x04;
+        assert_eq!(CartridgeHeader::parse(&rom).err(), Some(CartridgeError::UnknownCartridgeType(0x04)));
+        rom[CARTRIDGE_TYPE] = 0x00;
+        rom[ROM_SIZE] = 0x09;
+        assert_eq!(CartridgeHeader::parse(&rom).err(), Some(CartridgeError::UnknownRomSize(0x09)));
+        rom[ROM_SIZE] = 0x00;
+        rom[RAM_SIZE] = 0x06;
+        assert_eq!(CartridgeHeader::parse(&rom).err(), Some(CartridgeError::UnknownRamSize(0x06)));
+    }
+
+    #[test]
+    fn cartridge_type_display() {
+        // Run test and compare output
+        let cartridge_type = CartridgeType::from_code(0x10).unwrap();
+        assert_eq!(cartridge_type.to_string(), "MBC3+TIMER+RAM+BATTERY (0x10)");
+    }
+
+    #[test]
+    fn mbc1_multicart_on_gameboy() {
+        for multicart in [false, true] {
+            // Create a gameboy for testing purposes
+            let mut gameboy = Gameboy::new();
+
+            // Set up gameboy state for test, an 8 Mbit MBC1 ROM where the first byte of every bank
+            // holds its bank number. A multicart repeats the logo at the start of its second game.
+            let mut rom = build_rom(0x100000, 0x01, 0x05, 0x00);
+            for (bank, data) in rom.chunks_mut(0x4000).enumerate() {
+                data[0] = bank as u8;
+            }
+            rom[0x104..0x134].copy_from_slice(&[0xCE; 0x30]);
+            if multicart {
+                rom[0x40104..0x40134].copy_from_slice(&[0xCE; 0x30]);
+            }
+            gameboy.memory.load_cartridge(Cartridge::from_bytes(rom).unwrap());
+
+            // Run test and compare output, on a multicart BANK2 sits one bit lower
+            gameboy.write_instruction(0x4000, 0x01);
+            gameboy.write_instruction(0x2000, 0x02);
+            assert_eq!(gameboy.read_instruction(0x4000), if multicart { 0x12 } else { 0x22 });
+            gameboy.write_instruction(0x6000, 0x01);
+            assert_eq!(gameboy.read_instruction(0x0000), if multicart { 0x10 } else { 0x20 });
+        }
+    }
+
+    #[test]
+    fn rumble_visible_on_gameboy() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test, MBC5+RUMBLE+RAM
+        let rom = build_rom(0x8000, 0x1D, 0x00, 0x03);
+        gameboy.memory.load_cartridge(Cartridge::from_bytes(rom).unwrap());
+
+        // Run test and compare output
+        assert_eq!(gameboy.rumble(), false);
+        gameboy.write_instruction(0x4000, 0x08);
+        assert_eq!(gameboy.rumble(), true);
+        gameboy.write_instruction(0x4000, 0x00);
+        assert_eq!(gameboy.rumble(), false);
+    }
+
+    #[test]
+    fn mbc2_save_data() {
+        // Set up cartridge for test, MBC2+BATTERY
+        let rom = build_rom(0x40000, 0x06, 0x03, 0x00);
+        let mut cartridge = Cartridge::from_bytes(rom).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA1FF, 0x0C);
+
+        // Run test and compare output
+        let save = cartridge.save_data();
+        assert_eq!(save.len(), 0x200);
+        assert_eq!(save[0x1FF], 0x0C);
+        assert_eq!(cartridge.header.cartridge_type.battery, true);
+    }
+
+    #[test]
+    fn ram_disable_requests_flush() {
+        // Set up cartridge for test, MBC1+RAM+BATTERY
+        let rom = build_rom(0x10000, 0x03, 0x01, 0x02);
+        let mut cartridge = Cartridge::from_bytes(rom).unwrap();
+
+        // Run test and compare output
+        cartridge.write_ram(0xA000, 0x12);
+        assert_eq!(cartridge.ram_dirty, false);
+        cartridge.write_rom(0x0000, 0x00);
+        assert_eq!(cartridge.flush_requested, false);
+
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x12);
+        assert_eq!(cartridge.ram_dirty, true);
+        cartridge.write_rom(0x0000, 0x00);
+        assert_eq!(cartridge.flush_requested, true);
+    }
+
+    #[test]
+    fn ram_disable_without_writes() {
+        // Set up cartridge for test, MBC3+TIMER+RAM+BATTERY
+        let clock = FakeClock::new(1_700_000_000);
+        let rom = build_rom(0x8000, 0x10, 0x00, 0x02);
+        let mut cartridge = Cartridge::from_bytes_with_clock(rom, Box::new(clock.clone())).unwrap();
+
+        // Run test and compare output, reading the clock the way RTC games do every frame
+        for _ in 0..3 {
+            cartridge.write_rom(0x0000, 0x0A);
+            cartridge.write_rom(0x6000, 0x00);
+            cartridge.write_rom(0x6000, 0x01);
+            cartridge.write_rom(0x4000, 0x08);
+            cartridge.read_ram(0xA000);
+            cartridge.write_rom(0x0000, 0x00);
+            clock.advance(1);
+        }
+        assert_eq!(cartridge.ram_dirty, false);
+        assert_eq!(cartridge.flush_requested, false);
+    }
+
+    #[test]
+    fn unsupported_mapper() {
+        // Set up cartridge for test
+        let rom = build_rom(0x8000, 0xFC, 0x00, 0x00);
+
+        // Run test and compare output
+        let result = Cartridge::from_bytes(rom);
+        assert_eq!(result.err(), Some(CartridgeError::UnsupportedMapper(Mapper::PocketCamera)));
+    }
+
+    #[test]
+    fn rtc_save_data() {
+        // Set up cartridge for test
+        let clock = FakeClock::new(1_700_000_000);
+        let rom = build_rom(0x8000, 0x10, 0x00, 0x02);
+        let mut cartridge = Cartridge::from_bytes_with_clock(rom.clone(), Box::new(clock.clone())).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+        cartridge.write_rom(0x4000, 0x09);
+        cartridge.write_ram(0xA000, 0x07);
+
+        // Run test and compare output
+        let save = cartridge.save_data();
+        assert_eq!(save.len(), 0x2000 + 48);
+        assert_eq!(save[0], 0x42);
+        assert_eq!(save[0x2000 + 4], 0x07);
+
+        clock.advance(60);
+        let mut restored = Cartridge::from_bytes_with_clock(rom, Box::new(clock.clone())).unwrap();
+        restored.load_save_data(&save);
+        restored.write_rom(0x0000, 0x0A);
+        restored.write_rom(0x6000, 0x00);
+        restored.write_rom(0x6000, 0x01);
+        restored.write_rom(0x4000, 0x09);
+        assert_eq!(restored.read_ram(0xA000), 0x08);
+        restored.write_rom(0x4000, 0x00);
+        assert_eq!(restored.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn no_ram_reads_open_bus() {
+        // Set up cartridge for test
+        let mut cartridge = Cartridge::from_bytes(build_rom(0x8000, 0x00, 0x00, 0x00)).unwrap();
+
+        // Run test and compare output
+        cartridge.write_ram(0xA000, 0x12);
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+    }
+}
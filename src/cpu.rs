@@ -9,13 +9,23 @@ const CARRY_FLAG_BYTE_POSITION: u8 = 4;
 pub struct CPU {
     pub register: Registers,
     pub flags: FlagsRegister,
-    pub ime: bool
+    pub ime: bool,
+    // Set by EI, IME is enabled once the following instruction has executed
+    pub ime_pending: bool,
+    // Set by HALT until an enabled interrupt is requested
+    pub halted: bool,
+    // Set when HALT triggers the DMG bug so the next opcode fetch does not increment PC
+    pub halt_bug: bool,
+    // Set by STOP until a joypad line goes low
+    pub stopped: bool,
+    // CGB double speed mode, toggled by STOP when KEY1 is armed
+    pub double_speed: bool,
 }
 
 // Initialising CPU with zero values
 impl CPU {
     pub fn new() -> CPU {
-        let cpu = CPU {
+        CPU {
             register: Registers {
                 a: 0x0,
                 b: 0x0,
@@ -30,34 +40,15 @@ impl CPU {
             },
             flags: FlagsRegister { z: false, n: false, h: false, c: false },
             ime: false,
-        };
-        cpu
+            ime_pending: false,
+            halted: false,
+            halt_bug: false,
+            stopped: false,
+            double_speed: false,
+        }
     }
 }
 
-// // Initialising CPU with zero values
-// impl CPU {
-//     pub fn new() -> CPU {
-//         let cpu = CPU {
-//             register: Registers {
-//                 a: 0x01,
-//                 b: 0x0,
-//                 c: 0x13,
-//                 d: 0x0,
-//                 f: 0xB0,
-//                 e: 0xD8,
-//                 h: 0x01,
-//                 l: 0x4D,
-//                 pc: 0x100,
-//                 sp: 0xFFFE,
-//             },
-//             flags: FlagsRegister { z: false, n: false, h: false, c: false },
-//             ime: false,
-//         };
-//         cpu
-//     }
-// }
-
 #[derive(Copy, Clone)]
 pub struct FlagsRegister {
     // Zero flag
@@ -102,7 +93,6 @@ pub enum RegisterU16 {
     BC,
     DE,
     HL,
-    PC,
     SP
 }
 
@@ -130,7 +120,7 @@ pub enum InterruptConds {
 
 impl CPU {
     pub fn get_ime_state(&self) -> bool {
-        return self.ime;
+        self.ime
     }
 
     pub fn set_ime_state(&mut self, interrupt_condition: InterruptConds) {
@@ -186,7 +176,7 @@ impl Registers {
     }
 
     pub fn get_f_reg(&self, reg: FlagsRegister) -> u8 {
-        return reg.into();
+        reg.into()
     }
 
     pub fn update_f_reg(&mut self, val: FlagsRegister) { 
@@ -212,7 +202,6 @@ impl Registers {
             RegisterU16::BC => (self.b as u16) << 8 | self.c as u16,
             RegisterU16::DE => (self.d as u16) << 8 | self.e as u16,
             RegisterU16::HL => (self.h as u16) << 8 | self.l as u16,
-            RegisterU16::PC => self.pc,
             RegisterU16::SP => self.sp,
         }
     }
@@ -243,7 +232,6 @@ impl Registers {
                 self.write_u8(RegisterU8::L, lsb);
             }
 
-            RegisterU16::PC => self.pc = val,
             RegisterU16::SP => self.sp = val,
         }
     }
@@ -0,0 +1,136 @@
+use crate::mmu::IoDevice;
+
+pub const DMA: u16 = 0xFF46;
+
+// Bytes copied into OAM by one transfer, one per M-cycle
+const TRANSFER_LENGTH: u8 = 0xA0;
+
+// OAM DMA. Writing FF46 copies 160 bytes from XX00 into OAM, starting one M-cycle after the
+// write. While it runs the DMA owns the main bus, so the CPU only reaches I/O, HRAM and IE.
+pub struct OamDma {
+    // Last value written to FF46, the high byte of the source address
+    pub register: u8,
+    // Transfer requested by the last write, it takes over on the next M-cycle
+    pending: Option<u16>,
+    // Source address of the running transfer and how many bytes it has copied so far
+    active: Option<(u16, u8)>,
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        Self {
+            register: 0x00,
+            pending: None,
+            active: None,
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    // Whether the CPU is cut off from an address by the running transfer
+    pub fn blocks(&self, address: u16) -> bool {
+        self.active() && address < 0xFF00
+    }
+
+    // Advances by one M-cycle and returns the source address and OAM index to copy this cycle.
+    // A restarted transfer keeps the old one running through its setup cycle, so the bus stays
+    // blocked throughout.
+    pub fn step(&mut self) -> Option<(u16, usize)> {
+        let copy = self.active.map(|(source, index)| (source + index as u16, index as usize));
+        self.active = match self.active {
+            Some((source, index)) if index + 1 < TRANSFER_LENGTH => Some((source, index + 1)),
+            _ => None
+        };
+
+        if let Some(source) = self.pending.take() {
+            self.active = Some((source, 0));
+        }
+
+        copy
+    }
+}
+
+impl IoDevice for OamDma {
+    fn read(&self, _address: u16) -> u8 {
+        self.register
+    }
+
+    fn write(&mut self, _address: u16, data: u8) {
+        self.register = data;
+        self.pending = Some((data as u16) << 8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmu::MemoryBus;
+
+    // Fills WRAM at 0xC000 with a pattern and starts a transfer from there
+    fn start_transfer(bus: &mut MemoryBus) {
+        for offset in 0..0xA0 {
+            bus.write_byte(0xC000 + offset, offset as u8 ^ 0x5A);
+        }
+        bus.write_byte(DMA, 0xC0);
+    }
+
+    #[test]
+    fn transfer_timing() {
+        // Set up memory bus for test
+        let mut bus = MemoryBus::new();
+        start_transfer(&mut bus);
+
+        // Run test and compare output, one setup cycle then a byte per M-cycle
+        bus.tick(1);
+        assert_eq!(bus.dma.active(), true);
+        assert_eq!(bus.ppu.oam[0], 0x00);
+        bus.tick(1);
+        assert_eq!(bus.ppu.oam[0], 0x5A);
+        bus.tick(158);
+        assert_eq!(bus.dma.active(), true);
+        assert_eq!(bus.ppu.oam[0x9F], 0x00);
+        bus.tick(1);
+        assert_eq!(bus.dma.active(), false);
+        assert_eq!(bus.ppu.oam[0x9F], 0x9F ^ 0x5A);
+        assert_eq!(bus.read_byte(DMA), 0xC0);
+    }
+
+    #[test]
+    fn bus_conflicts() {
+        // Set up memory bus for test
+        let mut bus = MemoryBus::new();
+        bus.write_byte(0xFF80, 0x12);
+        start_transfer(&mut bus);
+        bus.tick(1);
+
+        // Run test and compare output, only I/O and HRAM are reachable
+        assert_eq!(bus.read_byte(0xC000), 0xFF);
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+        assert_eq!(bus.read_byte(0xFF80), 0x12);
+        bus.write_byte(0xC000, 0x00);
+        bus.tick(160);
+        assert_eq!(bus.read_byte(0xC000), 0x5A);
+    }
+
+    #[test]
+    fn restart() {
+        // Set up memory bus for test
+        let mut bus = MemoryBus::new();
+        start_transfer(&mut bus);
+        for offset in 0..0xA0 {
+            bus.write_byte(0xD000 + offset, 0xEE);
+        }
+        bus.tick(11);
+
+        // Run test and compare output, the first transfer copies one more byte then starts over
+        bus.write_byte(DMA, 0xD0);
+        bus.tick(1);
+        assert_eq!(bus.ppu.oam[10], 10 ^ 0x5A);
+        assert_eq!(bus.read_byte(0xC000), 0xFF);
+        bus.tick(160);
+        assert_eq!(bus.dma.active(), false);
+        assert_eq!(bus.ppu.oam[..], [0xEE; 0xA0][..]);
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use pixels::{Pixels, SurfaceTexture};
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::WindowBuilder;
+
+use crate::joypad::Button;
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// 70224 dots per frame at 4.194304 MHz, about 59.73 frames a second
+const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706);
+
+const SCALE: u32 = 3;
+
+const TITLE: &str = "gb_emulator";
+// Shown while the cartridge's rumble motor is running, there's no motor to drive
+const RUMBLE_TITLE: &str = "gb_emulator (rumble)";
+
+// RGBA for the four DMG shades, lightest first
+const SHADES: [[u8; 4]; 4] = [
+    [0xFF, 0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+    [0x00, 0x00, 0x00, 0xFF],
+];
+
+// What the window needs from the emulator
+pub trait Emulation {
+    // Emulates up to the end of the next frame, returns false once the emulator wants to quit
+    fn run_frame(&mut self) -> bool;
+    // One shade 0 - 3 per pixel
+    fn framebuffer(&self) -> &[u8];
+    fn set_button(&mut self, button: Button, pressed: bool);
+    // Whether the cartridge's rumble motor is running
+    fn rumble(&self) -> bool;
+    // Called once before the process exits
+    fn shutdown(&mut self);
+}
+
+// Keyboard layout, arrows for the d-pad with X and Z as A and B
+fn button_for_key(key: VirtualKeyCode) -> Option<Button> {
+    match key {
+        VirtualKeyCode::Right => Some(Button::Right),
+        VirtualKeyCode::Left => Some(Button::Left),
+        VirtualKeyCode::Up => Some(Button::Up),
+        VirtualKeyCode::Down => Some(Button::Down),
+        VirtualKeyCode::X => Some(Button::A),
+        VirtualKeyCode::Z => Some(Button::B),
+        VirtualKeyCode::Back => Some(Button::Select),
+        VirtualKeyCode::Return => Some(Button::Start),
+        _ => None
+    }
+}
+
+// Opens a window and runs the emulator in it at the Game Boy's frame rate until it is closed
+pub fn run_window(mut emulation: impl Emulation + 'static) -> ! {
+    let event_loop = EventLoop::new();
+    let size = LogicalSize::new(SCREEN_WIDTH as u32 * SCALE, SCREEN_HEIGHT as u32 * SCALE);
+    let window = WindowBuilder::new()
+        .with_title(TITLE)
+        .with_inner_size(size)
+        .with_min_inner_size(LogicalSize::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32))
+        .build(&event_loop)
+        .expect("Failed to open window");
+
+    let window_size = window.inner_size();
+    let surface = SurfaceTexture::new(window_size.width, window_size.height, &window);
+    let mut pixels = Pixels::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, surface)
+        .expect("Failed to create rendering surface");
+
+    let mut next_frame = Instant::now();
+    let mut rumble = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        match event {
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => *control_flow = ControlFlow::Exit,
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. }
+                if pixels.resize_surface(size.width, size.height).is_err() => *control_flow = ControlFlow::Exit,
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput { virtual_keycode: Some(key), state, .. },
+                    ..
+                },
+                ..
+            } => {
+                if let Some(button) = button_for_key(key) {
+                    emulation.set_button(button, state == ElementState::Pressed);
+                }
+            }
+            Event::MainEventsCleared => {
+                if Instant::now() >= next_frame {
+                    if !emulation.run_frame() {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    next_frame += FRAME_DURATION;
+                    if emulation.rumble() != rumble {
+                        rumble = !rumble;
+                        window.set_title(if rumble { RUMBLE_TITLE } else { TITLE });
+                    }
+                    window.request_redraw();
+                }
+                *control_flow = ControlFlow::WaitUntil(next_frame);
+            }
+            Event::RedrawRequested(_) => {
+                for (pixel, shade) in pixels.frame_mut().chunks_exact_mut(4).zip(emulation.framebuffer()) {
+                    pixel.copy_from_slice(&SHADES[*shade as usize]);
+                }
+                if pixels.render().is_err() {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            Event::LoopDestroyed => emulation.shutdown(),
+            _ => {}
+        }
+    })
+}
@@ -1,132 +1,138 @@
+use crate::boot::Model;
 use crate::cpu::*;
+use crate::joypad::Button;
 use crate::mmu::MemoryBus;
-use crate::timer::Timer;
+use crate::serial::SerialEndpoint;
+use crate::timer::DIV;
 
 pub struct Gameboy {
     pub cpu: CPU,
     pub memory: MemoryBus,
-    pub timer: Timer,
-    // clock: u8
+    // Total M-cycles executed since power on
+    pub cycles: u64,
+    // Hardware revision being emulated
+    pub model: Model,
+    // M-cycles of the current instruction the rest of the hardware has already been advanced through
+    ticked: u8,
 }
 
 impl Gameboy {
     pub fn new() -> Gameboy {
-        let gameboy = Gameboy {
+        Gameboy {
             cpu: CPU::new(),
             memory: MemoryBus::new(),
-            timer: Timer::new(),
-        };
-        gameboy
-    }
-
-    fn handle_timer(&mut self, cycle: usize) {
-        
-        // set divider 
-        self.timer.div_clocksum += cycle;
-        if self.timer.div_clocksum >= 256 {
-            self.timer.div_clocksum -= 256;
-            self.timer.div_reg = self.read_instruction(0xFF04);
-            self.timer.div_reg = self.timer.div_reg.wrapping_add(1);
-            self.write_instruction(0xFF04, self.timer.div_reg);
-        }
-
-        self.timer.tac_reg = self.read_instruction(0xFF07);
-        if ((self.timer.tac_reg >> 2) & 0x1) != 0 {
-            self.timer.timer_clocksum += cycle * 4;
-        }
-
-
-        // set clock speed of CPU
-        let mut freq = 4096;
-
-        if (self.timer.tac_reg & 3) == 1 {
-            freq = 262144;
-        }
-        else if (self.timer.tac_reg & 3) == 2 {
-            freq = 65536;
-        }
-        else if (self.timer.tac_reg & 3) == 3 {
-            freq = 16384;
-        }
-
-        while self.timer.div_clocksum >= (4194394 / freq) {
-
+            cycles: 0,
+            model: Model::Dmg,
+            ticked: 0,
         }
     }
 
-    fn handle_interrupt(&mut self) {
-        if self.cpu.get_ime_state() {
-            let mut ie_flag = self.read_instruction(0xFFFF);
-            let mut if_flag = self.read_instruction(0xFF0F);
-
-            if (ie_flag & if_flag) != 0 {
+    pub fn read_instruction(&self, address: u16) -> u8 {
+        self.memory.read_byte(address)
+    }
 
-                // v-blank interrupt
-                if ((ie_flag & 1) & (if_flag & 1)) != 0 {
-                    let [lsb_pc, msb_pc] = self.cpu.register.pc.to_le_bytes();
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, msb_pc);
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, lsb_pc);
+    pub fn write_instruction(&mut self, address: u16, data: u8) {
+        self.memory.write_byte(address, data);
+    }
 
-                    self.cpu.register.pc = 0x40;
+    // Reads and writes the CPU makes while running an instruction. Each takes an M-cycle, and the
+    // rest of the hardware is advanced through it first so the access sees it as it is by then.
+    pub fn read_cycle(&mut self, address: u16) -> u8 {
+        self.memory.tick(1);
+        self.ticked += 1;
+        self.memory.read_byte(address)
+    }
 
-                    self.write_instruction(0xFF0F, if_flag &! 1);
-                }
+    pub fn write_cycle(&mut self, address: u16, data: u8) {
+        self.memory.tick(1);
+        self.ticked += 1;
+        self.memory.write_byte(address, data);
+    }
 
-                ie_flag = self.read_instruction(0xFFFF);
-                if_flag = self.read_instruction(0xFF0F);
+    // Picks the hardware revision to emulate, call before booting
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.memory.io.cgb = model == Model::Cgb;
+    }
 
-                // lcd stat interrupt
-                if ((ie_flag & 2) & (if_flag & 2)) != 0 {
-                    let [lsb_pc, msb_pc] = self.cpu.register.pc.to_le_bytes();
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, msb_pc);
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, lsb_pc);
+    // Whether the cartridge's rumble motor is running, always false for carts without one
+    pub fn rumble(&self) -> bool {
+        self.memory.cartridge.as_ref().is_some_and(|cartridge| cartridge.rumble())
+    }
 
-                    self.cpu.register.pc = 0x48;
+    // Presses or releases a button, for frontends and tests to feed in input
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.memory.joypad.set_button(button, pressed);
+    }
 
-                    self.write_instruction(0xFF0F, if_flag &! 2);
-                }
+    // Plugs something into the link port in place of whatever was there before
+    pub fn connect_serial(&mut self, endpoint: Box<dyn SerialEndpoint>) {
+        self.memory.serial.connect(endpoint);
+    }
 
-                ie_flag = self.read_instruction(0xFFFF);
-                if_flag = self.read_instruction(0xFF0F);
+    // Runs a single instruction, services any pending interrupt and returns the number of M-cycles it took
+    pub fn fetch(&mut self) -> u8 {
+        // Cycles spent on memory accesses have been ticked as they happened, the internal ones
+        // are made up at the end
+        self.ticked = 0;
+        let mut cycles = self.run_instruction();
+        self.memory.tick(cycles - self.ticked);
 
-                // timer interrupt
-                if ((ie_flag & 4) & (if_flag & 4)) != 0 {
-                    let [lsb_pc, msb_pc] = self.cpu.register.pc.to_le_bytes();
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, msb_pc);
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, lsb_pc);
+        // Interrupts are checked after every instruction
+        self.ticked = 0;
+        let interrupt_cycles = self.handle_interrupt();
+        self.memory.tick(interrupt_cycles - self.ticked);
+        cycles += interrupt_cycles;
 
-                    self.cpu.register.pc = 0x50;
+        self.cycles += cycles as u64;
+        cycles
+    }
 
-                    self.write_instruction(0xFF0F, if_flag &! 4);
-                }
+    fn run_instruction(&mut self) -> u8 {
+        // A halted CPU idles until an enabled interrupt is requested, regardless of IME
+        if self.cpu.halted {
+            if self.pending_interrupts() == 0 {
+                return 1;
+            }
+            self.cpu.halted = false;
 
-                self.cpu.set_ime_state(InterruptConds::Disabled);
+            // With IME set the CPU wakes straight into the interrupt handler
+            if self.cpu.get_ime_state() {
+                return 1;
             }
         }
-    }
 
-    pub fn read_instruction(&self, address: u16) -> u8 {
-        return self.memory.read_byte(address);
-    }
-
-    pub fn write_instruction(&mut self, address: u16, data: u8) {
-        self.memory.write_byte(address, data);
-    }
+        // A stopped CPU idles until one of the joypad input lines goes low
+        if self.cpu.stopped {
+            if self.read_instruction(0xFF00) & 0x0F == 0x0F {
+                return 1;
+            }
+            self.cpu.stopped = false;
+        }
 
-    pub fn fetch (&mut self) {
-        let opcode = self.read_instruction(self.cpu.register.pc);
+        let opcode = self.read_cycle(self.cpu.register.pc);
         // println!("PC: {:#X}", self.cpu.register.pc);
         // println!("Opcode: {:#X} \nA: {:#X} F: {:#X} B: {:#X} C: {:#X} D: {:#X} E: {:#X} H: {:#X} L: {:#X} SP: {:#X} PC: {:#X}", opcode, self.cpu.register.a,
         // self.cpu.register.f, self.cpu.register.b, self.cpu.register.c, self.cpu.register.d, self.cpu.register.e, self.cpu.register.h, self.cpu.register.l, self.cpu.register.sp,
         // self.cpu.register.pc);
-        // self.cpu.register.pc += 1;
-        self.execute(opcode);
+        if self.cpu.halt_bug {
+            self.cpu.halt_bug = false;
+        }
+        else {
+            self.cpu.register.pc += 1;
+        }
+
+        // An ei from the previous instruction takes effect once this one has run, unless it was cancelled by di
+        let ime_pending = self.cpu.ime_pending;
+
+        let cycles = self.execute(opcode);
+
+        if ime_pending && self.cpu.ime_pending {
+            self.cpu.ime_pending = false;
+            self.cpu.set_ime_state(InterruptConds::Enabled);
+        }
+
+        cycles
     }
 
     fn _half_carry_add_u16(&self, val_1: u16, val_2: u16) -> bool {
@@ -145,7 +151,17 @@ impl Gameboy {
         (val_1 & 0xF) < (val_2 & 0xF)
     }
 
-    fn execute(&mut self, opcode: u8) {
+    // Evaluates the condition code used by the conditional jump, call and return instructions
+    fn check_condition(&self, cond: FlagConds) -> bool {
+        match cond {
+            FlagConds::NZ => !self.cpu.flags.get_flag(Flag::Z),
+            FlagConds::Z => self.cpu.flags.get_flag(Flag::Z),
+            FlagConds::NC => !self.cpu.flags.get_flag(Flag::C),
+            FlagConds::C => self.cpu.flags.get_flag(Flag::C)
+        }
+    }
+
+    fn execute(&mut self, opcode: u8) -> u8 {
         // Opcode table: https://izik1.github.io/gbops/index.html
         match opcode {
             // 0x0 opcodes
@@ -167,7 +183,7 @@ impl Gameboy {
             0x0F => self.rrca(),
 
             // 0x1 opcodes
-            0x10 => todo!(),
+            0x10 => self.stop(),
             0x11 => self.ld_rr_nn(RegisterU16::DE),
             0x12 => self.ld_rr_a(RegisterU16::DE),
             0x13 => self.inc_rr(RegisterU16::DE),
@@ -281,7 +297,7 @@ impl Gameboy {
             0x73 => self.ld_hl_r(RegisterU8::E),
             0x74 => self.ld_hl_r(RegisterU8::H),
             0x75 => self.ld_hl_r(RegisterU8::L),
-            0x76 => todo!(),
+            0x76 => self.halt(),
             0x77 => self.ld_hl_r(RegisterU8::A),
             0x78 => self.ld_r_r(RegisterU8::A, RegisterU8::B),
             0x79 => self.ld_r_r(RegisterU8::A, RegisterU8::C),
@@ -377,7 +393,7 @@ impl Gameboy {
             0xC9 => self.ret(),
             0xCA => self.jp_cc_nn(FlagConds::Z),
             0xCB => self.cb_prefix(),
-            0xCC => self.jp_cc_nn(FlagConds::Z),
+            0xCC => self.call_cc_nn(FlagConds::Z),
             0xCD => self.call_nn(),
             0xCE => self.adc_n(),
             0xCF => self.rst_n(0x08),
@@ -392,7 +408,7 @@ impl Gameboy {
             0xD6 => self.sub_n(),
             0xD7 => self.rst_n(0x10),
             0xD8 => self.ret_cc(FlagConds::C),
-            0xD9 => todo!(),
+            0xD9 => self.reti(),
             0xDA => self.jp_cc_nn(FlagConds::C),
             0xDB => panic!("Illegal Opcode: {:#X}", opcode),
             0xDC => self.call_cc_nn(FlagConds::C),
@@ -422,7 +438,7 @@ impl Gameboy {
             0xF0 => self.ldh_a_n(),
             0xF1 => self.pop(RegisterU16::AF),
             0xF2 => self.ldh_a_c(),
-            0xF3 => self.ei(),
+            0xF3 => self.di(),
             0xF4 => panic!("Illegal Opcode: {:#X}", opcode),
             0xF5 => self.push(RegisterU16::AF),
             0xF6 => self.or_n(),
@@ -430,7 +446,7 @@ impl Gameboy {
             0xF8 => self.ld_hl_sp_e(),
             0xF9 => self.ld_sp_hl(),
             0xFA => self.ld_a_nn(),
-            0xFB => self.di(),
+            0xFB => self.ei(),
             0xFC => panic!("Illegal Opcode: {:#X}", opcode),
             0xFD => panic!("Illegal Opcode: {:#X}", opcode),
             0xFE => self.cp_n(),
@@ -438,8 +454,8 @@ impl Gameboy {
         }
     }
 
-fn cb_prefix(&mut self) {
-    let cb_code = self.read_instruction(self.cpu.register.pc);
+fn cb_prefix(&mut self) -> u8 {
+    let cb_code = self.read_cycle(self.cpu.register.pc);
     self.cpu.register.pc += 1;
 
     match cb_code {
@@ -736,218 +752,261 @@ fn cb_prefix(&mut self) {
     // CPU instructions
     // Instructions intepreted from https://gekkio.fi/files/gb-docs/gbctr.pdf
     // and https://rgbds.gbdev.io/docs/v0.6.1/gbz80.7/
-    fn nop(&self) { }
+    fn nop(&self) -> u8 { 1 }
 
     // 8 bit load instructions
-    fn ld_r_r(&mut self, r1: RegisterU8, r2: RegisterU8) {
+    fn ld_r_r(&mut self, r1: RegisterU8, r2: RegisterU8) -> u8 {
         let reg2 = self.cpu.register.read_u8(r2);
-        self.cpu.register.write_u8(r1, reg2)
-        
+        self.cpu.register.write_u8(r1, reg2);
+
+        1
     }
 
-    fn ld_r_n(&mut self, r1: RegisterU8) {
-        let n = self.read_instruction(self.cpu.register.pc);
+    fn ld_r_n(&mut self, r1: RegisterU8) -> u8 {
+        let n = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
-        self.cpu.register.write_u8(r1, n)
+        self.cpu.register.write_u8(r1, n);
+
+        2
     }
 
-    fn ld_r_hl(&mut self, r1: RegisterU8) {
+    fn ld_r_hl(&mut self, r1: RegisterU8) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
         self.cpu.register.write_u8(r1, data);
+
+        2
     }
 
-    fn ld_hl_r(&mut self, r1: RegisterU8) {
+    fn ld_hl_r(&mut self, r1: RegisterU8) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
         let data = self.cpu.register.read_u8(r1);
-        self.write_instruction(address, data);
+        self.write_cycle(address, data);
+
+        2
     }
 
-    fn ld_hl_n(&mut self) {
+    fn ld_hl_n(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        self.write_instruction(address, data);
+        self.write_cycle(address, data);
+
+        3
     }
 
     // ld_a_bc/ld_a_de
-    fn ld_a_rr(&mut self, r1: RegisterU16) {
+    fn ld_a_rr(&mut self, r1: RegisterU16) -> u8 {
         let address = self.cpu.register.read_u16(r1);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
         self.cpu.register.write_u8(RegisterU8::A, data);
+
+        2
     }
 
     // ld_bc_a/ld_de_a
-    fn ld_rr_a(&mut self, r1: RegisterU16) {
+    fn ld_rr_a(&mut self, r1: RegisterU16) -> u8 {
         let address = self.cpu.register.read_u16(r1);
         let data = self.cpu.register.read_u8(RegisterU8::A);
-        self.write_instruction(address, data);
+        self.write_cycle(address, data);
+
+        2
     }
 
-    fn ld_a_nn(&mut self) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn ld_a_nn(&mut self) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let nn = (msb as u16) << 8 | lsb as u16;
-        let data = self.read_instruction(nn);
+        let data = self.read_cycle(nn);
         self.cpu.register.write_u8(RegisterU8::A, data);
+
+        4
     }
 
-    fn ld_nn_a(&mut self) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn ld_nn_a(&mut self) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let reg_data = self.cpu.register.read_u8(RegisterU8::A);
         let nn = (msb as u16) << 8 | lsb as u16;
 
-        self.write_instruction(nn, reg_data);
+        self.write_cycle(nn, reg_data);
+
+        4
     }
 
-    fn ldh_a_c(&mut self) {
+    fn ldh_a_c(&mut self) -> u8 {
         let msb: u16 = 0xFF00;
         let lsb = self.cpu.register.read_u8(RegisterU8::C) as u16;
         let address = msb | lsb;
 
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         self.cpu.register.write_u8(RegisterU8::A, data);
+
+        2
     }
 
-    fn ldh_c_a(&mut self) {
+    fn ldh_c_a(&mut self) -> u8 {
         let msb: u16 = 0xFF00;
         let lsb = self.cpu.register.read_u8(RegisterU8::C) as u16;
         let address = msb | lsb;
 
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
 
-        self.write_instruction(address, reg_a);
+        self.write_cycle(address, reg_a);
+
+        2
     }
 
-    fn ldh_a_n(&mut self) {
+    fn ldh_a_n(&mut self) -> u8 {
         let msb: u16 = 0xFF00;
-        let lsb = self.read_instruction(self.cpu.register.pc) as u16;
+        let lsb = self.read_cycle(self.cpu.register.pc) as u16;
         self.cpu.register.pc += 1;
 
         let address = msb | lsb;
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         self.cpu.register.write_u8(RegisterU8::A, data);
+
+        3
     }
 
-    fn ldh_n_a(&mut self) {
+    fn ldh_n_a(&mut self) -> u8 {
         let msb: u16 = 0xFF00;
-        let lsb = self.read_instruction(self.cpu.register.pc) as u16;
+        let lsb = self.read_cycle(self.cpu.register.pc) as u16;
         self.cpu.register.pc += 1;
         let address = msb | lsb;
 
         let data = self.cpu.register.read_u8(RegisterU8::A);
-        self.write_instruction(address, data);
+        self.write_cycle(address, data);
+
+        3
     }
 
-    fn ld_a_hl_minus(&mut self) {
+    fn ld_a_hl_minus(&mut self) -> u8 {
         let mut address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         address = address.wrapping_sub(1);
         self.cpu.register.write_u16(RegisterU16::HL, address);
 
         self.cpu.register.write_u8(RegisterU8::A, data);
+
+        2
     }
 
-    fn ld_hl_minus_a(&mut self) {
+    fn ld_hl_minus_a(&mut self) -> u8 {
         let mut address = self.cpu.register.read_u16(RegisterU16::HL);
         let data = self.cpu.register.read_u8(RegisterU8::A);
 
-        self.write_instruction(address, data);
+        self.write_cycle(address, data);
         address = address.wrapping_sub(1);
 
         self.cpu.register.write_u16(RegisterU16::HL, address);
+
+        2
     }
 
-    fn ld_a_hl_plus(&mut self) {
+    fn ld_a_hl_plus(&mut self) -> u8 {
         let mut address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         self.cpu.register.write_u8(RegisterU8::A, data);
 
         address = address.wrapping_add(1);
         self.cpu.register.write_u16(RegisterU16::HL, address);
+
+        2
     }
 
-    fn ld_hl_plus_a(&mut self) {
+    fn ld_hl_plus_a(&mut self) -> u8 {
         let reg_data = self.cpu.register.read_u8(RegisterU8::A);
         let mut address = self.cpu.register.read_u16(RegisterU16::HL);
 
-        self.write_instruction(address, reg_data);
+        self.write_cycle(address, reg_data);
 
         address = address.wrapping_add(1);
         self.cpu.register.write_u16(RegisterU16::HL, address);
+
+        2
     }
 
     // 16 bit load instructions
-    fn ld_rr_nn(&mut self, r1: RegisterU16) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn ld_rr_nn(&mut self, r1: RegisterU16) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let nn = (msb as u16) << 8 | lsb as u16;
         self.cpu.register.write_u16(r1, nn);
+
+        3
     }
 
-    fn ld_nn_sp(&mut self) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn ld_nn_sp(&mut self) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let nn = (msb as u16) << 8 | lsb as u16;
 
         let [sp_lsb, sp_msb] = self.cpu.register.sp.to_le_bytes();
 
-        self.write_instruction(nn, sp_lsb);
-        self.write_instruction(nn.wrapping_add(1), sp_msb);
+        self.write_cycle(nn, sp_lsb);
+        self.write_cycle(nn.wrapping_add(1), sp_msb);
+
+        5
     }
 
-    fn ld_sp_hl(&mut self) {
+    fn ld_sp_hl(&mut self) -> u8 {
         let reg_data = self.cpu.register.read_u16(RegisterU16::HL);
         self.cpu.register.sp = reg_data;
+
+        2
     }
 
-    fn push(&mut self, r1: RegisterU16) {
+    fn push(&mut self, r1: RegisterU16) -> u8 {
         let reg_data = self.cpu.register.read_u16(r1);
         let [lsb, msb] = reg_data.to_le_bytes();
 
         self.cpu.register.sp -= 1;
-        self.write_instruction(self.cpu.register.sp, msb);
+        self.write_cycle(self.cpu.register.sp, msb);
         self.cpu.register.sp -= 1;
-        self.write_instruction(self.cpu.register.sp, lsb);
+        self.write_cycle(self.cpu.register.sp, lsb);
+
+        4
     }
 
-    fn pop(&mut self, r1: RegisterU16) {
-        let lsb = self.read_instruction(self.cpu.register.sp);
+    fn pop(&mut self, r1: RegisterU16) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.sp);
         self.cpu.register.sp += 1;
         
-        let msb = self.read_instruction(self.cpu.register.sp);
+        let msb = self.read_cycle(self.cpu.register.sp);
         self.cpu.register.sp += 1;
 
         let data = (msb as u16) << 8 | lsb as u16;
         self.cpu.register.write_u16(r1, data);
+
+        3
     }
 
     // ALU Instructions
 
     // 8 bit ALU
-    fn add_r(&mut self, r1: RegisterU8) {
+    fn add_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let reg_data = self.cpu.register.read_u8(r1);
 
@@ -967,12 +1026,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn add_hl(&mut self) {
+    fn add_hl(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let (result, carry_flag) = reg_a.overflowing_add(data);
         let half_carry_flag = self._half_carry_add_u8(reg_a, data);
@@ -990,11 +1051,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn add_n(&mut self) {
+    fn add_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let (result, carry_flag) = reg_a.overflowing_add(data);
@@ -1013,9 +1076,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn adc_r(&mut self, r1: RegisterU8) {
+    fn adc_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let reg_data = self.cpu.register.read_u8(r1);
         let f_reg = self.cpu.register.get_f_reg(self.cpu.flags);
@@ -1046,12 +1111,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn adc_hl(&mut self) {
+    fn adc_hl(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
         let f_reg = self.cpu.register.get_f_reg(self.cpu.flags);
 
         let carry_flag_u8 = f_reg & 0b0001_0000;
@@ -1080,11 +1147,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn adc_n(&mut self) {
+    fn adc_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let f_reg = self.cpu.register.get_f_reg(self.cpu.flags);
@@ -1114,9 +1183,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn sub_r(&mut self, r1: RegisterU8) {
+    fn sub_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let reg_data = self.cpu.register.read_u8(r1);
 
@@ -1137,12 +1208,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn sub_hl(&mut self) {
+    fn sub_hl(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let (result, carry_flag) = reg_a.overflowing_sub(data);
 
@@ -1161,11 +1234,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn sub_n(&mut self) {
+    fn sub_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let (result, carry_flag) = reg_a.overflowing_sub(data);
@@ -1185,9 +1260,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn sbc_r(&mut self, r1: RegisterU8) {
+    fn sbc_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let reg_data = self.cpu.register.read_u8(r1);
         let f_reg = self.cpu.register.get_f_reg(self.cpu.flags);
@@ -1218,12 +1295,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn sbc_hl(&mut self) {
+    fn sbc_hl(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
         let f_reg = self.cpu.register.get_f_reg(self.cpu.flags);
 
         let carry_flag_u8 = f_reg & 0b0001_0000;
@@ -1252,11 +1331,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn sbc_n(&mut self) {
+    fn sbc_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let f_reg = self.cpu.register.get_f_reg(self.cpu.flags);
@@ -1286,9 +1367,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn cp_r(&mut self, r1: RegisterU8) {
+    fn cp_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let reg_data = self.cpu.register.read_u8(r1);
 
@@ -1306,13 +1389,15 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn cp_hl(&mut self) {
+    fn cp_hl(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
 
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let (result, carry_flag) = reg_a.overflowing_sub(data);
         let half_carry_flag = self._half_carry_sub_u8(reg_a, data);
@@ -1328,11 +1413,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn cp_n(&mut self) {
+    fn cp_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let (result, carry_flag): (u8, bool) = reg_a.overflowing_sub(data);
@@ -1350,9 +1437,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn inc_r(&mut self, r1: RegisterU8) {
+    fn inc_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data = self.cpu.register.read_u8(r1);
         let half_carry_flag = self._half_carry_add_u8(reg_data, 1);
 
@@ -1369,16 +1458,18 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn inc_hl(&mut self) {
+    fn inc_hl(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let mut data = self.read_instruction(address);
+        let mut data = self.read_cycle(address);
 
         let half_carry_flag = self._half_carry_add_u8(data, 1);
 
         data = data.wrapping_add(0x1);
-        self.write_instruction(address, data);
+        self.write_cycle(address, data);
 
         if data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -1390,9 +1481,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        3
     }
 
-    fn dec_r(&mut self, r1: RegisterU8) {
+    fn dec_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data = self.cpu.register.read_u8(r1);
         let half_carry_flag = self._half_carry_sub_u8(reg_data, 0x1);
 
@@ -1409,16 +1502,18 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, true);
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn dec_hl(&mut self) {
+    fn dec_hl(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let mut data = self.read_instruction(address);
+        let mut data = self.read_cycle(address);
 
         let half_carry_flag = self._half_carry_sub_u8(data, 1);
 
         data = data.wrapping_sub(0x1);
-        self.write_instruction(address, data);
+        self.write_cycle(address, data);
 
         if data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -1430,9 +1525,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, true);
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        3
     }
 
-    fn and_r(&mut self, r1: RegisterU8) {
+    fn and_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let reg_data = self.cpu.register.read_u8(r1);
 
@@ -1451,12 +1548,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, true);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn and_hl(&mut self) {
+    fn and_hl(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let result = reg_a & data;
 
@@ -1473,11 +1572,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, true);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn and_n(&mut self) {
+    fn and_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let result = reg_a & data;
@@ -1495,9 +1596,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, true);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn or_r(&mut self, r1: RegisterU8) {
+    fn or_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let reg_data = self.cpu.register.read_u8(r1);
 
@@ -1516,12 +1619,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn or_hl(&mut self) {
+    fn or_hl(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let result = reg_a | data;
 
@@ -1538,11 +1643,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn or_n(&mut self) {
+    fn or_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let result = reg_a | data;
@@ -1560,9 +1667,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn xor_r(&mut self, r1: RegisterU8) {
+    fn xor_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data = self.cpu.register.read_u8(r1);
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
 
@@ -1580,11 +1689,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn xor_hl(&mut self) {
+    fn xor_hl(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
 
         let result = reg_a ^ data;
@@ -1601,11 +1712,13 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn xor_n(&mut self) {
+    fn xor_n(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
-        let data = self.read_instruction(self.cpu.register.pc);
+        let data = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let result = reg_a ^ data;
@@ -1622,9 +1735,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn ccf(&mut self) {
+    fn ccf(&mut self) -> u8 {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
 
@@ -1638,17 +1753,21 @@ fn cb_prefix(&mut self) {
         }
 
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn scf(&mut self) {
+    fn scf(&mut self) -> u8 {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, true);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
     // Need to check line 1360 ||
-    fn daa(&mut self) {
+    fn daa(&mut self) -> u8 {
         let mut reg_a = self.cpu.register.read_u8(RegisterU8::A);
 
         let n_flag = self.cpu.flags.get_flag(Flag::N);
@@ -1679,21 +1798,25 @@ fn cb_prefix(&mut self) {
         self.cpu.register.write_u8(RegisterU8::A, reg_a);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn cpl(&mut self) {
+    fn cpl(&mut self) -> u8 {
         let mut reg_a = self.cpu.register.read_u8(RegisterU8::A);
 
-        reg_a = reg_a ^ 0xFF;
+        reg_a ^= 0xFF;
         self.cpu.register.write_u8(RegisterU8::A, reg_a);
 
         self.cpu.flags.set_flag(Flag::N, true);
         self.cpu.flags.set_flag(Flag::H, true);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
     // 16 bit ALU
-    fn add_hl_rr(&mut self, r1: RegisterU16) {
+    fn add_hl_rr(&mut self, r1: RegisterU16) -> u8 {
         let reg_hl = self.cpu.register.read_u16(RegisterU16::HL);
         let reg_data = self.cpu.register.read_u16(r1);
 
@@ -1707,24 +1830,30 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn inc_rr(&mut self, r1: RegisterU16) {
+    fn inc_rr(&mut self, r1: RegisterU16) -> u8 {
         let reg_data = self.cpu.register.read_u16(r1);
         let reg_data = reg_data.wrapping_add(0x01);
 
         self.cpu.register.write_u16(r1, reg_data);
+
+        2
     }
 
-    fn dec_rr(&mut self, r1: RegisterU16) {
+    fn dec_rr(&mut self, r1: RegisterU16) -> u8 {
         let reg_data = self.cpu.register.read_u16(r1);
         let reg_data = reg_data.wrapping_sub(0x01);
 
         self.cpu.register.write_u16(r1, reg_data);
+
+        2
     }
 
-    fn add_sp_e(&mut self) {
-        let offset = self.read_instruction(self.cpu.register.pc) as i8;
+    fn add_sp_e(&mut self) -> u8 {
+        let offset = self.read_cycle(self.cpu.register.pc) as i8;
         self.cpu.register.pc += 1;
         let new_sp = self.cpu.register.sp;
 
@@ -1738,12 +1867,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
     // Essentially the same as add_sp_e but saves the result to the HL register
     // Not sure if there is a more elegant way than repeating code
-    fn ld_hl_sp_e(&mut self) {
-        let offset = self.read_instruction(self.cpu.register.pc) as i8;
+    fn ld_hl_sp_e(&mut self) -> u8 {
+        let offset = self.read_cycle(self.cpu.register.pc) as i8;
         self.cpu.register.pc += 1;
         let new_sp = self.cpu.register.sp;
 
@@ -1757,113 +1888,79 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, half_carry_flag);
         self.cpu.flags.set_flag(Flag::C, carry_flag);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        3
     }
 
     // Control flow instructions
-    fn jp_nn(&mut self) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn jp_nn(&mut self) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let nn = (msb as u16) << 8 | lsb as u16;
         self.cpu.register.pc = nn;
+
+        4
     }
 
-    fn jp_hl(&mut self) {
+    fn jp_hl(&mut self) -> u8 {
         self.cpu.register.pc = self.cpu.register.read_u16(RegisterU16::HL);
+
+        1
     }
 
-    fn jp_cc_nn(&mut self, jp_cond: FlagConds) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn jp_cc_nn(&mut self, jp_cond: FlagConds) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let nn = (msb as u16) << 8 | lsb as u16;
 
-        match jp_cond {
-            FlagConds::NZ => {
-                if !self.cpu.flags.get_flag(Flag::Z) {
-                    self.cpu.register.pc = nn;
-                }
-            },
-
-            FlagConds::Z => {
-                if self.cpu.flags.get_flag(Flag::Z) {
-                    self.cpu.register.pc = nn;
-                }
-            },
-
-            FlagConds::NC => {
-                if !self.cpu.flags.get_flag(Flag::C) {
-                    self.cpu.register.pc = nn;
-                }
-            },
-
-            FlagConds::C => {
-                if self.cpu.flags.get_flag(Flag::C) {
-                    self.cpu.register.pc = nn;
-                }
-            }
+        if self.check_condition(jp_cond) {
+            self.cpu.register.pc = nn;
+            4
+        }
+        else {
+            3
         }
     }
 
-    fn jr_e(&mut self) {
-        let offset = self.read_instruction(self.cpu.register.pc) as i8;
+    fn jr_e(&mut self) -> u8 {
+        let offset = self.read_cycle(self.cpu.register.pc) as i8;
         self.cpu.register.pc += 1;
         let mut new_pc = self.cpu.register.pc;
 
         new_pc = new_pc.wrapping_add_signed(offset as i16);
         self.cpu.register.pc = new_pc;
+
+        3
     }
 
-    fn jr_cc_e(&mut self, jp_cond: FlagConds) {
-        let offset = self.read_instruction(self.cpu.register.pc) as i8;
+    fn jr_cc_e(&mut self, jp_cond: FlagConds) -> u8 {
+        let offset = self.read_cycle(self.cpu.register.pc) as i8;
         self.cpu.register.pc += 1;
 
-        match jp_cond {
-            FlagConds::NZ => {
-                if !self.cpu.flags.get_flag(Flag::Z) {
-                    let mut new_pc = self.cpu.register.pc;
-                    new_pc = new_pc.wrapping_add_signed(offset as i16);
-                    self.cpu.register.pc = new_pc;
-                }
-            },
-
-            FlagConds::Z => {
-                if self.cpu.flags.get_flag(Flag::Z) {
-                    let mut new_pc = self.cpu.register.pc;
-                    new_pc = new_pc.wrapping_add_signed(offset as i16);
-                    self.cpu.register.pc = new_pc;
-                }
-            },
-
-            FlagConds::NC => {
-                if !self.cpu.flags.get_flag(Flag::C) {
-                    let mut new_pc = self.cpu.register.pc;
-                    new_pc = new_pc.wrapping_add_signed(offset as i16);
-                    self.cpu.register.pc = new_pc;
-                }
-            },
-
-            FlagConds::C => {
-                if self.cpu.flags.get_flag(Flag::C) {
-                    let mut new_pc = self.cpu.register.pc;
-                    new_pc = new_pc.wrapping_add_signed(offset as i16);
-                    self.cpu.register.pc = new_pc;
-                }
-            }
+        if self.check_condition(jp_cond) {
+            let mut new_pc = self.cpu.register.pc;
+            new_pc = new_pc.wrapping_add_signed(offset as i16);
+            self.cpu.register.pc = new_pc;
+            3
+        }
+        else {
+            2
         }
     }
 
-    fn call_nn(&mut self) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn call_nn(&mut self) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let nn = (msb as u16) << 8 | lsb as u16;
@@ -1871,141 +1968,89 @@ fn cb_prefix(&mut self) {
         let [lsb_pc, msb_pc] = self.cpu.register.pc.to_le_bytes();
 
         self.cpu.register.sp -= 1;
-        self.write_instruction(self.cpu.register.sp, msb_pc);
+        self.write_cycle(self.cpu.register.sp, msb_pc);
         self.cpu.register.sp -= 1;
-        self.write_instruction(self.cpu.register.sp, lsb_pc);
+        self.write_cycle(self.cpu.register.sp, lsb_pc);
         self.cpu.register.pc = nn;
+
+        6
     }
 
-    fn call_cc_nn(&mut self, jp_cond: FlagConds) {
-        let lsb = self.read_instruction(self.cpu.register.pc);
+    fn call_cc_nn(&mut self, jp_cond: FlagConds) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
-        let msb = self.read_instruction(self.cpu.register.pc);
+        let msb = self.read_cycle(self.cpu.register.pc);
         self.cpu.register.pc += 1;
 
         let nn = (msb as u16) << 8 | lsb as u16;
 
         let [lsb_pc, msb_pc] = self.cpu.register.pc.to_le_bytes();
 
-        match jp_cond {
-            FlagConds::NZ => {
-                if !self.cpu.flags.get_flag(Flag::Z) {
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, msb_pc);
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, lsb_pc);
-                    self.cpu.register.pc = nn;
-                }
-            },
-
-            FlagConds::Z => {
-                if self.cpu.flags.get_flag(Flag::Z) {
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, msb_pc);
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, lsb_pc);
-                    self.cpu.register.pc = nn;
-                }
-            },
-
-            FlagConds::NC => {
-                if !self.cpu.flags.get_flag(Flag::C) {
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, msb_pc);
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, lsb_pc);
-                    self.cpu.register.pc = nn;
-                }
-            },
-
-            FlagConds::C => {
-                if self.cpu.flags.get_flag(Flag::C) {
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, msb_pc);
-                    self.cpu.register.sp -= 1;
-                    self.write_instruction(self.cpu.register.sp, lsb_pc);
-                    self.cpu.register.pc = nn;
-                }
-            }
+        if self.check_condition(jp_cond) {
+            self.cpu.register.sp -= 1;
+            self.write_cycle(self.cpu.register.sp, msb_pc);
+            self.cpu.register.sp -= 1;
+            self.write_cycle(self.cpu.register.sp, lsb_pc);
+            self.cpu.register.pc = nn;
+            6
+        }
+        else {
+            3
         }
     }
 
-    fn ret(&mut self) {
-        let lsb = self.read_instruction(self.cpu.register.sp);
+    fn ret(&mut self) -> u8 {
+        let lsb = self.read_cycle(self.cpu.register.sp);
         self.cpu.register.sp += 1;
         
-        let msb = self.read_instruction(self.cpu.register.sp);
+        let msb = self.read_cycle(self.cpu.register.sp);
         self.cpu.register.sp += 1;
 
         self.cpu.register.pc = (msb as u16) << 8 | lsb as u16;
+
+        4
     }
 
-    fn ret_cc(&mut self, jp_cond: FlagConds) {
-        match jp_cond {
-            FlagConds::NZ => {
-                if !self.cpu.flags.get_flag(Flag::Z) {
-                    let lsb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-                    
-                    let msb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-            
-                    self.cpu.register.pc = (msb as u16) << 8 | lsb as u16;
-                }
-            },
-
-            FlagConds::Z => {
-                if self.cpu.flags.get_flag(Flag::Z) {
-                    let lsb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-                    
-                    let msb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-            
-                    self.cpu.register.pc = (msb as u16) << 8 | lsb as u16;
-                }
-            },
-
-            FlagConds::NC => {
-                if !self.cpu.flags.get_flag(Flag::C) {
-                    let lsb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-                    
-                    let msb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-            
-                    self.cpu.register.pc = (msb as u16) << 8 | lsb as u16;
-                }
-            },
-
-            FlagConds::C => {
-                if self.cpu.flags.get_flag(Flag::C) {
-                    let lsb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-                    
-                    let msb = self.read_instruction(self.cpu.register.sp);
-                    self.cpu.register.sp += 1;
-            
-                    self.cpu.register.pc = (msb as u16) << 8 | lsb as u16;
-                }
-            }
+    fn ret_cc(&mut self, jp_cond: FlagConds) -> u8 {
+        if self.check_condition(jp_cond) {
+            let lsb = self.read_cycle(self.cpu.register.sp);
+            self.cpu.register.sp += 1;
+
+            let msb = self.read_cycle(self.cpu.register.sp);
+            self.cpu.register.sp += 1;
+
+            self.cpu.register.pc = (msb as u16) << 8 | lsb as u16;
+            5
+        }
+        else {
+            2
         }
     }
 
-    fn rst_n(&mut self, jp_addr: u8) {
+    // Unlike ei, reti enables interrupts without any delay
+    fn reti(&mut self) -> u8 {
+        self.ret();
+        self.cpu.set_ime_state(InterruptConds::Enabled);
+
+        4
+    }
+
+    fn rst_n(&mut self, jp_addr: u8) -> u8 {
         let [lsb_pc, msb_pc] = self.cpu.register.pc.to_le_bytes();
 
         self.cpu.register.sp -= 1;
-        self.write_instruction(self.cpu.register.sp, msb_pc);
+        self.write_cycle(self.cpu.register.sp, msb_pc);
         self.cpu.register.sp -= 1;
-        self.write_instruction(self.cpu.register.sp, lsb_pc);
+        self.write_cycle(self.cpu.register.sp, lsb_pc);
         self.cpu.register.pc = jp_addr as u16;
+
+        4
     }
 
 
     // Rotate, shift and bit operations
-    fn rla(&mut self) {
+    fn rla(&mut self) -> u8 {
         let carry_flag = self.cpu.flags.get_flag(Flag::C);
         let reg_data = self.cpu.register.read_u8(RegisterU8::A);
 
@@ -2013,7 +2058,7 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let mut new_reg_data = reg_data << 1;
-        new_reg_data = new_reg_data & 0b1111_1110;
+        new_reg_data &= 0b1111_1110;
 
         if carry_flag {
             new_reg_data += 1;
@@ -2027,9 +2072,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn rlca(&mut self) {
+    fn rlca(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
 
         let new_carry_flag: bool = (reg_a & 0b1000_0000) != 0;
@@ -2042,9 +2089,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn rlc_r(&mut self, r1: RegisterU8) {
+    fn rlc_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data =  self.cpu.register.read_u8(r1);
 
         let new_carry_flag: bool = (reg_data & 0b1000_0000) != 0;
@@ -2063,17 +2112,19 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn rlc_hl(&mut self) {
+    fn rlc_hl(&mut self) -> u8 {
         let address =  self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_carry_flag: bool = (data & 0b1000_0000) != 0;
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let rot_data = data.rotate_left(1);
-        self.write_instruction(address, rot_data);
+        self.write_cycle(address, rot_data);
 
         if rot_data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -2085,9 +2136,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn rl_r(&mut self, r1: RegisterU8) {
+    fn rl_r(&mut self, r1: RegisterU8) -> u8 {
         let carry_flag = self.cpu.flags.get_flag(Flag::C);
         let reg_data = self.cpu.register.read_u8(r1);
 
@@ -2095,7 +2148,7 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let mut new_reg_data = reg_data << 1;
-        new_reg_data = new_reg_data & 0b1111_1110;
+        new_reg_data &= 0b1111_1110;
 
         if carry_flag {
             new_reg_data += 1;
@@ -2114,25 +2167,27 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn rl_hl(&mut self) {
+    fn rl_hl(&mut self) -> u8 {
         let carry_flag = self.cpu.flags.get_flag(Flag::C);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_carry_flag: bool = (data & 0b1000_0000) != 0;
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let mut new_data = data << 1;
-        new_data = new_data & 0b1111_1110;
+        new_data &= 0b1111_1110;
 
         if carry_flag {
             new_data += 1;
-            self.write_instruction(address, new_data);
+            self.write_cycle(address, new_data);
         }
         else {
-            self.write_instruction(address, new_data);
+            self.write_cycle(address, new_data);
         }
 
         if new_data == 0 {
@@ -2145,9 +2200,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn sla_r(&mut self, r1: RegisterU8) {
+    fn sla_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data = self.cpu.register.read_u8(r1);
 
         let new_carry_flag: bool = (reg_data & 0b1000_0000) != 0;
@@ -2167,18 +2224,20 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn sla_hl(&mut self) {
+    fn sla_hl(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_carry_flag: bool = (data & 0b1000_0000) != 0;
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let shifted_reg_data = data << 1;
 
-        self.write_instruction(address, shifted_reg_data);
+        self.write_cycle(address, shifted_reg_data);
 
         if shifted_reg_data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -2190,9 +2249,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn rra(&mut self) {
+    fn rra(&mut self) -> u8 {
         let carry_flag = self.cpu.flags.get_flag(Flag::C);
         let reg_data = self.cpu.register.read_u8(RegisterU8::A);
 
@@ -2200,7 +2261,7 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let mut new_reg_data = reg_data >> 1;
-        new_reg_data = new_reg_data & 0b0111_1111;
+        new_reg_data &= 0b0111_1111;
 
         if carry_flag {
             new_reg_data += 0b1000_0000;
@@ -2214,9 +2275,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn rrca(&mut self) {
+    fn rrca(&mut self) -> u8 {
         let reg_a = self.cpu.register.read_u8(RegisterU8::A);
 
         let new_carry_flag: bool = (reg_a & 0b0000_0001) != 0;
@@ -2229,9 +2292,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        1
     }
 
-    fn rrc_r(&mut self, r1: RegisterU8) {
+    fn rrc_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data =  self.cpu.register.read_u8(r1);
 
         let new_carry_flag: bool = (reg_data & 0b0000_0001) != 0;
@@ -2250,17 +2315,19 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn rrc_hl(&mut self) {
+    fn rrc_hl(&mut self) -> u8 {
         let address =  self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_carry_flag: bool = (data & 0b0000_0001) != 0;
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let rot_data = data.rotate_right(1);
-        self.write_instruction(address, rot_data);
+        self.write_cycle(address, rot_data);
 
         if rot_data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -2272,9 +2339,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn rr_r(&mut self, r1: RegisterU8) {
+    fn rr_r(&mut self, r1: RegisterU8) -> u8 {
         let carry_flag = self.cpu.flags.get_flag(Flag::C);
         let reg_data = self.cpu.register.read_u8(r1);
 
@@ -2282,7 +2351,7 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let mut new_reg_data = reg_data >> 1;
-        new_reg_data = new_reg_data & 0b0111_1111;
+        new_reg_data &= 0b0111_1111;
 
         if carry_flag {
             new_reg_data += 0b1000_0000;
@@ -2302,25 +2371,27 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn rr_hl(&mut self) {
+    fn rr_hl(&mut self) -> u8 {
         let carry_flag = self.cpu.flags.get_flag(Flag::C);
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_carry_flag: bool = (data & 0b0000_0001) != 0;
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let mut new_data = data >> 1;
-        new_data = new_data & 0b0111_1111;
+        new_data &= 0b0111_1111;
 
         if carry_flag {
             new_data += 0b1000_0000;
-            self.write_instruction(address, new_data);
+            self.write_cycle(address, new_data);
         }
         else {
-            self.write_instruction(address, new_data);
+            self.write_cycle(address, new_data);
         }
 
         if new_data == 0 {
@@ -2333,9 +2404,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn sra_r(&mut self, r1: RegisterU8) {
+    fn sra_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data = self.cpu.register.read_u8(r1);
 
         let new_carry_flag: bool = (reg_data & 0b0000_0001) != 0;
@@ -2359,20 +2432,22 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn sra_hl(&mut self) {
+    fn sra_hl(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_carry_flag: bool = (data & 0b0000_0001) != 0;
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let msb = data & 0b1000_0000;
         let mut shifted_reg_data = data >> 1;
-        shifted_reg_data = shifted_reg_data | msb;
+        shifted_reg_data |= msb;
 
-        self.write_instruction(address, shifted_reg_data);
+        self.write_cycle(address, shifted_reg_data);
 
         if shifted_reg_data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -2384,9 +2459,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn srl_r(&mut self, r1: RegisterU8) {
+    fn srl_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data = self.cpu.register.read_u8(r1);
 
         let new_carry_flag: bool = (reg_data & 0b0000_0001) != 0;
@@ -2405,17 +2482,19 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn srl_hl(&mut self) {
+    fn srl_hl(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_carry_flag: bool = (data & 0b0000_0001) != 0;
         self.cpu.flags.set_flag(Flag::C, new_carry_flag);
 
         let shifted_reg_data = data >> 1;
-        self.write_instruction(address, shifted_reg_data);
+        self.write_cycle(address, shifted_reg_data);
 
         if shifted_reg_data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -2427,9 +2506,11 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn swap_r(&mut self, r1: RegisterU8) {
+    fn swap_r(&mut self, r1: RegisterU8) -> u8 {
         let reg_data = self.cpu.register.read_u8(r1);
 
         let new_lsb = reg_data >> 4;
@@ -2450,18 +2531,20 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn swap_hl(&mut self) {
+    fn swap_hl(&mut self) -> u8 {
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let data = self.read_instruction(address);
+        let data = self.read_cycle(address);
 
         let new_lsb = data >> 4;
         let new_msb = data << 4;
 
         let swapped_reg_data = new_msb | new_lsb;
 
-        self.write_instruction(address, swapped_reg_data);
+        self.write_cycle(address, swapped_reg_data);
 
         if swapped_reg_data == 0 {
             self.cpu.flags.set_flag(Flag::Z, true);
@@ -2474,12 +2557,14 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, false);
         self.cpu.flags.set_flag(Flag::C, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        4
     }
 
-    fn bit_r(&mut self, check_bit: u8, r1: RegisterU8) {
+    fn bit_r(&mut self, check_bit: u8, r1: RegisterU8) -> u8 {
         let mask: u8 = 1;
         let mut reg_data = self.cpu.register.read_u8(r1);
-        reg_data = reg_data >> check_bit;
+        reg_data >>= check_bit;
 
         if (reg_data & mask) == 1 {
             self.cpu.flags.set_flag(Flag::Z, false);
@@ -2492,13 +2577,15 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, true);
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        2
     }
 
-    fn bit_hl(&mut self, check_bit: u8) {
+    fn bit_hl(&mut self, check_bit: u8) -> u8 {
         let mask: u8 = 1;
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let mut data = self.read_instruction(address);
-        data = data >> check_bit;
+        let mut data = self.read_cycle(address);
+        data >>= check_bit;
 
         if (data & mask) == 1 {
             self.cpu.flags.set_flag(Flag::Z, false);
@@ -2511,51 +2598,95 @@ fn cb_prefix(&mut self) {
         self.cpu.flags.set_flag(Flag::H, true);
         self.cpu.flags.set_flag(Flag::N, false);
         self.cpu.register.update_f_reg(self.cpu.flags);
+
+        3
     }
 
-    fn res_r(&mut self, reset_bit: u8, r1: RegisterU8) {
+    fn res_r(&mut self, reset_bit: u8, r1: RegisterU8) -> u8 {
         let mask: u8 = 1;
         let mut reg_data = self.cpu.register.read_u8(r1);
 
-        reg_data = reg_data & !(mask << reset_bit);
+        reg_data &= !(mask << reset_bit);
         self.cpu.register.write_u8(r1, reg_data);
+
+        2
     }
 
-    fn res_hl(&mut self, reset_bit: u8) {
+    fn res_hl(&mut self, reset_bit: u8) -> u8 {
         let mask: u8 = 1;
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let mut data = self.read_instruction(address);
+        let mut data = self.read_cycle(address);
+
+        data &= !(mask << reset_bit);
+        self.write_cycle(address, data);
 
-        data = data & !(mask << reset_bit);
-        self.write_instruction(address, data);
+        4
     }
 
-    fn set_r(&mut self, set_bit: u8, r1: RegisterU8) {
+    fn set_r(&mut self, set_bit: u8, r1: RegisterU8) -> u8 {
         let mask: u8 = 1;
         let mut reg_data = self.cpu.register.read_u8(r1);
 
-        reg_data = reg_data | (mask << set_bit);
+        reg_data |= mask << set_bit ;
         self.cpu.register.write_u8(r1, reg_data);
+
+        2
     }
 
-    fn set_hl(&mut self, set_bit: u8) {
+    fn set_hl(&mut self, set_bit: u8) -> u8 {
         let mask: u8 = 1;
         let address = self.cpu.register.read_u16(RegisterU16::HL);
-        let mut data = self.read_instruction(address);
+        let mut data = self.read_cycle(address);
 
-        data = data | (mask << set_bit);
-        self.write_instruction(address, data);
+        data |= mask << set_bit ;
+        self.write_cycle(address, data);
+
+        4
     }
 
-    fn ei(&mut self) {
+    fn halt(&mut self) -> u8 {
+        // With IME off and an interrupt already pending the CPU does not halt, and the
+        // following opcode fetch fails to increment PC (the DMG HALT bug)
+        if !self.cpu.get_ime_state() && self.pending_interrupts() != 0 {
+            self.cpu.halt_bug = true;
+        }
+        else {
+            self.cpu.halted = true;
+        }
+
+        1
+    }
+
+    fn stop(&mut self) -> u8 {
+        // STOP is followed by a padding byte which is skipped
         self.cpu.register.pc += 1;
-        self.fetch();
 
-        self.cpu.set_ime_state(InterruptConds::Enabled);
+        self.write_instruction(DIV, 0);
+
+        // On CGB an armed KEY1 turns STOP into a speed switch instead of entering low power mode
+        let key1 = self.read_instruction(0xFF4D);
+        if self.model == Model::Cgb && (key1 & 1) != 0 {
+            self.cpu.double_speed = !self.cpu.double_speed;
+            self.write_instruction(0xFF4D, if self.cpu.double_speed { 0x80 } else { 0x00 });
+        }
+        else {
+            self.cpu.stopped = true;
+        }
+
+        1
     }
 
-    fn di(&mut self) {
+    fn ei(&mut self) -> u8 {
+        self.cpu.ime_pending = true;
+
+        1
+    }
+
+    fn di(&mut self) -> u8 {
+        self.cpu.ime_pending = false;
         self.cpu.set_ime_state(InterruptConds::Disabled);
+
+        1
     }
 }
 
@@ -2568,6 +2699,7 @@ fn cb_prefix(&mut self) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_roms::run_blargg;
 
     // ld tests
     #[test]
@@ -2596,9 +2728,9 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set pc and memory addresses
-        gameboy.cpu.register.pc = 1;
-        gameboy.memory.write_byte(0, 0x01);
-        gameboy.memory.write_byte(1, 0x02);
+        gameboy.cpu.register.pc = 0xC001;
+        gameboy.memory.write_byte(0xC000, 0x01);
+        gameboy.memory.write_byte(0xC001, 0x02);
 
         // Run load value from memory into register
         gameboy.ld_r_n(r1);
@@ -2616,7 +2748,8 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set up gameboy state for test
-        gameboy.cpu.register.write_u8(RegisterU8::H, 0x11);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u8(RegisterU8::H, 0xD1);
         gameboy.cpu.register.write_u8(RegisterU8::L, 0x11);
         gameboy.write_instruction(gameboy.cpu.register.read_u16(RegisterU16::HL), 0x01);
 
@@ -2634,7 +2767,8 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set up gameboy state for test
-        gameboy.cpu.register.write_u8(RegisterU8::H, 0x11);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u8(RegisterU8::H, 0xD1);
         gameboy.cpu.register.write_u8(RegisterU8::L, 0x11);
         gameboy.cpu.register.write_u8(r1, 0x01);
 
@@ -2650,8 +2784,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.cpu.register.write_u16(RegisterU16::HL, 0x1111);
-        gameboy.write_instruction(0x0, 0x01);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xD111);
+        gameboy.write_instruction(0xC000, 0x01);
 
         // Run test and compare output
         gameboy.ld_hl_n();
@@ -2700,9 +2835,10 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.write_instruction(0xFFFE, 0x01);
-        gameboy.write_instruction(0x0, 0xFE);
-        gameboy.write_instruction(0x01, 0xFF);
+        gameboy.write_instruction(0xC000, 0xFE);
+        gameboy.write_instruction(0xC001, 0xFF);
 
         // Run test and compare output
         gameboy.ld_a_nn();
@@ -2717,9 +2853,10 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.cpu.register.write_u8(RegisterU8::A, 0x01);
-        gameboy.write_instruction(0x0, 0xFE);
-        gameboy.write_instruction(0x01, 0xFF);
+        gameboy.write_instruction(0xC000, 0xFE);
+        gameboy.write_instruction(0xC001, 0xFF);
 
         // Run test and compare output
         gameboy.ld_nn_a();
@@ -2767,7 +2904,8 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0xFA);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xFA);
         gameboy.write_instruction(0xFFFA, 0x01);
 
         // Run test and compare output
@@ -2812,17 +2950,18 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set up gameboy state for test
-        gameboy.cpu.register.write_u8(RegisterU8::H, 0x11);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u8(RegisterU8::H, 0xD1);
         gameboy.cpu.register.write_u8(RegisterU8::L, 0x11);
         gameboy.cpu.register.write_u8(r1, 0x01);
 
         // Run test and compare output
         gameboy.ld_hl_minus_a();
-        let data_in_memory = gameboy.read_instruction(0x1111);
+        let data_in_memory = gameboy.read_instruction(0xD111);
         let new_hl = gameboy.cpu.register.read_u16(RegisterU16::HL);
 
         assert_eq!(data_in_memory, 0x01);
-        assert_eq!(new_hl, 0x1110);
+        assert_eq!(new_hl, 0xD110);
     }
 
     #[test]
@@ -2832,9 +2971,10 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set up gameboy state for test
-        gameboy.cpu.register.write_u8(RegisterU8::H, 0x11);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u8(RegisterU8::H, 0xD1);
         gameboy.cpu.register.write_u8(RegisterU8::L, 0x11);
-        gameboy.write_instruction(0x1111, 0x01);
+        gameboy.write_instruction(0xD111, 0x01);
 
         // Run test and compare output
         gameboy.ld_a_hl_plus();
@@ -2843,7 +2983,7 @@ mod tests {
         let new_hl = gameboy.cpu.register.read_u16(RegisterU16::HL);
 
         assert_eq!(reg_data, 0x01);
-        assert_eq!(new_hl, 0x1112);
+        assert_eq!(new_hl, 0xD112);
     }
 
     #[test]
@@ -2873,11 +3013,11 @@ mod tests {
         let sp = RegisterU16::SP;
 
         // Set up gameboy state for test
-        gameboy.cpu.register.write_u16(RegisterU16::PC, 0);
-        gameboy.memory.write_byte(0, 0x01);
-        gameboy.memory.write_byte(1, 0x02);
-        gameboy.memory.write_byte(2, 0xFF);
-        gameboy.memory.write_byte(3, 0xFF);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.memory.write_byte(0xC000, 0x01);
+        gameboy.memory.write_byte(0xC001, 0x02);
+        gameboy.memory.write_byte(0xC002, 0xFF);
+        gameboy.memory.write_byte(0xC003, 0xFF);
 
         // Run test and compare output
         gameboy.ld_rr_nn(r1);
@@ -2897,15 +3037,16 @@ mod tests {
         let sp = RegisterU16::SP;
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.cpu.register.write_u16(sp, 0xFFFE);
-        gameboy.write_instruction(0x0, 0x01);
-        gameboy.write_instruction(0x1, 0x02);
+        gameboy.write_instruction(0xC000, 0x01);
+        gameboy.write_instruction(0xC001, 0xC2);
 
         // Run test and compare output
         gameboy.ld_nn_sp();
 
-        let sp_lsb = gameboy.read_instruction(0x201);
-        let sp_msb = gameboy.read_instruction(0x202);
+        let sp_lsb = gameboy.read_instruction(0xC201);
+        let sp_msb = gameboy.read_instruction(0xC202);
 
         assert_eq!(sp_lsb, 0xFE);
         assert_eq!(sp_msb, 0xFF);
@@ -2997,8 +3138,10 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.register.write_u8(RegisterU8::A, 0xFF);
-        gameboy.write_instruction(0x0, 0x1);
+        gameboy.write_instruction(0xC000, 0x1);
 
         // Run test and compare output
         gameboy.add_hl();
@@ -3023,7 +3166,8 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0xFF);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xFF);
 
         // Run test and compare output
         gameboy.add_n();
@@ -3074,8 +3218,10 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.register.write_u8(RegisterU8::A, 0xFF);
-        gameboy.write_instruction(0x0, 0x01);
+        gameboy.write_instruction(0xC000, 0x01);
         gameboy.cpu.flags.set_flag(Flag::C, true);
 
         // Run test and compare output
@@ -3100,8 +3246,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.cpu.register.write_u8(RegisterU8::A, 0xFF);
-        gameboy.write_instruction(0x0, 0x01);
+        gameboy.write_instruction(0xC000, 0x01);
         gameboy.cpu.flags.set_flag(Flag::C, true);
 
         // Run test and compare output
@@ -3150,7 +3297,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x1);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0x1);
 
         // Run test and compare output
         gameboy.sub_hl();
@@ -3174,7 +3323,8 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x1);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x1);
 
         // Run test and compare output
         gameboy.sub_n();
@@ -3223,7 +3373,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x01);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0x01);
         gameboy.cpu.flags.set_flag(Flag::C, true);
 
         // Run test and compare output
@@ -3248,7 +3400,8 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x01);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x01);
         gameboy.cpu.flags.set_flag(Flag::C, true);
 
         // Run test and compare output
@@ -3318,7 +3471,8 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x1);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x1);
 
         // Run test and compare output
         gameboy.cp_n();
@@ -3362,11 +3516,13 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0xFF);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0xFF);
 
         // Run test and compare output
         gameboy.inc_hl();
-        let data_in_memory = gameboy.read_instruction(0x0);
+        let data_in_memory = gameboy.read_instruction(0xC000);
         let hc_flag = gameboy.cpu.flags.get_flag(Flag::H); 
         let n_flag = gameboy.cpu.flags.get_flag(Flag::N);
         let z_flag = gameboy.cpu.flags.get_flag(Flag::Z);
@@ -3400,8 +3556,12 @@ mod tests {
         // Create a gameboy for testing purposes
         let mut gameboy = Gameboy::new();
 
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0xFF);
+
         gameboy.dec_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let hc_flag = gameboy.cpu.flags.get_flag(Flag::H); 
         let n_flag = gameboy.cpu.flags.get_flag(Flag::N);
         let z_flag = gameboy.cpu.flags.get_flag(Flag::Z);
@@ -3443,8 +3603,10 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.register.write_u8(RegisterU8::A, 0xFA);
-        gameboy.write_instruction(0x0, 0xCD);
+        gameboy.write_instruction(0xC000, 0xCD);
 
         // Run test and compare output
         gameboy.and_hl();
@@ -3468,8 +3630,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.cpu.register.write_u8(RegisterU8::A, 0xFA);
-        gameboy.write_instruction(0x0, 0xCD);
+        gameboy.write_instruction(0xC000, 0xCD);
 
         // Run test and compare output
         gameboy.and_n();
@@ -3518,8 +3681,10 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.register.write_u8(RegisterU8::A, 0xF0);
-        gameboy.write_instruction(0x0, 0x0F);
+        gameboy.write_instruction(0xC000, 0x0F);
 
         // Run test and compare output
         gameboy.or_hl();
@@ -3543,8 +3708,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.cpu.register.write_u8(RegisterU8::A, 0xF0);
-        gameboy.write_instruction(0x0, 0x0F);
+        gameboy.write_instruction(0xC000, 0x0F);
 
         // Run test and compare output
         gameboy.or_n();
@@ -3587,9 +3753,10 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.cpu.register.write_u8(r1, 0xFF);
-        gameboy.cpu.register.write_u16(RegisterU16::HL, 0x01);
-        gameboy.write_instruction(0x01, 0xF0);
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC001);
+        gameboy.write_instruction(0xC001, 0xF0);
 
         // Run test and compare output
         gameboy.xor_hl();
@@ -3608,8 +3775,9 @@ mod tests {
         let r1 = RegisterU8::A;
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
         gameboy.cpu.register.write_u8(r1, 0xFF);
-        gameboy.write_instruction(0x0, 0xF0);
+        gameboy.write_instruction(0xC000, 0xF0);
 
         // Run test and compare output
         gameboy.xor_n();
@@ -3741,7 +3909,8 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x1);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x1);
         gameboy.cpu.register.sp = 0xFFFF;
 
         // Run test and compare output
@@ -3761,7 +3930,8 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x1);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x1);
         gameboy.cpu.register.sp = 0xFFFD;
 
         // Run test and compare output
@@ -3782,8 +3952,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0xFB);
-        gameboy.write_instruction(0x1, 0xFA);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xFB);
+        gameboy.write_instruction(0xC001, 0xFA);
 
         // Run test and compare output
         gameboy.jp_nn();
@@ -3813,8 +3984,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0xFB);
-        gameboy.write_instruction(0x1, 0xFA);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xFB);
+        gameboy.write_instruction(0xC001, 0xFA);
         gameboy.cpu.flags.set_flag(Flag::Z, false);
 
         // Run test and compare output
@@ -3830,13 +4002,13 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.cpu.register.pc = 0x0B;
-        gameboy.write_instruction(0x0B, 0xFB);
+        gameboy.cpu.register.pc = 0xC00B;
+        gameboy.write_instruction(0xC00B, 0xFB);
 
         // Run test and compare output
         gameboy.jr_e();
         let new_pc = gameboy.cpu.register.pc;
-        assert_eq!(new_pc, 0x07);
+        assert_eq!(new_pc, 0xC007);
     }
 
     #[test]
@@ -3845,15 +4017,15 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.cpu.register.pc = 0x0B;
-        gameboy.write_instruction(0x0B, 0xFB);
+        gameboy.cpu.register.pc = 0xC00B;
+        gameboy.write_instruction(0xC00B, 0xFB);
         gameboy.cpu.flags.set_flag(Flag::C, false);
 
         // Run test and compare output
         gameboy.jr_cc_e(FlagConds::NC);
 
         let new_pc = gameboy.cpu.register.pc;
-        assert_eq!(new_pc, 0x07);
+        assert_eq!(new_pc, 0xC007);
     }
 
     #[test]
@@ -3862,8 +4034,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x01);
-        gameboy.write_instruction(0x01, 0x02);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x01);
+        gameboy.write_instruction(0xC001, 0x02);
         gameboy.cpu.register.sp = 0xFFFE;
 
         // Run test and compare output
@@ -3876,7 +4049,7 @@ mod tests {
 
         assert_eq!(new_sp, 0xFFFC);
         assert_eq!(new_pc, 0x0201);
-        assert_eq!(msb, 0x0);
+        assert_eq!(msb, 0xC0);
         assert_eq!(lsb, 0x02);
     }
 
@@ -3886,8 +4059,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x01);
-        gameboy.write_instruction(0x01, 0x02);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x01);
+        gameboy.write_instruction(0xC001, 0x02);
         gameboy.cpu.register.sp = 0xFFFE;
         gameboy.cpu.flags.set_flag(Flag::C, false);
 
@@ -3901,7 +4075,7 @@ mod tests {
 
         assert_eq!(new_sp, 0xFFFC);
         assert_eq!(new_pc, 0x0201);
-        assert_eq!(msb, 0x0);
+        assert_eq!(msb, 0xC0);
         assert_eq!(lsb, 0x02);
     }
 
@@ -4022,12 +4196,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.flags.set_flag(Flag::C, true);
-        gameboy.write_instruction(0x0, 0b1101_0010);
+        gameboy.write_instruction(0xC000, 0b1101_0010);
 
         // Run test and compare output
         gameboy.rlc_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let carry_flag = gameboy.cpu.flags.get_flag(Flag::C);
 
         assert_eq!(new_r1, 0b1010_0101);
@@ -4059,12 +4235,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.flags.set_flag(Flag::C, true);
-        gameboy.write_instruction(0x0, 0b1101_0010);
+        gameboy.write_instruction(0xC000, 0b1101_0010);
 
         // Run test and compare output
         gameboy.rl_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let carry_flag = gameboy.cpu.flags.get_flag(Flag::C);
 
         assert_eq!(new_r1, 0b1010_0101);
@@ -4096,12 +4274,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.flags.set_flag(Flag::C, true);
-        gameboy.write_instruction(0x0, 0b1101_0010);
+        gameboy.write_instruction(0xC000, 0b1101_0010);
 
         // Run test and compare output
         gameboy.sla_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let carry_flag = gameboy.cpu.flags.get_flag(Flag::C);
 
         assert_eq!(new_r1, 0b1010_0100);
@@ -4151,12 +4331,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.flags.set_flag(Flag::C, true);
-        gameboy.write_instruction(0x0, 0b1101_0010);
+        gameboy.write_instruction(0xC000, 0b1101_0010);
 
         // Run test and compare output
         gameboy.rrc_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let carry_flag = gameboy.cpu.flags.get_flag(Flag::C);
 
         assert_eq!(new_r1, 0b0110_1001);
@@ -4206,12 +4388,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.flags.set_flag(Flag::C, true);
-        gameboy.write_instruction(0x0, 0b1101_0010);
+        gameboy.write_instruction(0xC000, 0b1101_0010);
 
         // Run test and compare output
         gameboy.rr_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let carry_flag = gameboy.cpu.flags.get_flag(Flag::C);
 
         assert_eq!(new_r1, 0b1110_1001);
@@ -4243,12 +4427,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.flags.set_flag(Flag::C, true);
-        gameboy.write_instruction(0x0, 0b1101_0010);
+        gameboy.write_instruction(0xC000, 0b1101_0010);
 
         // Run test and compare output
         gameboy.sra_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let carry_flag = gameboy.cpu.flags.get_flag(Flag::C);
 
         assert_eq!(new_r1, 0b1110_1001);
@@ -4280,12 +4466,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
         gameboy.cpu.flags.set_flag(Flag::C, true);
-        gameboy.write_instruction(0x0, 0b1101_0010);
+        gameboy.write_instruction(0xC000, 0b1101_0010);
 
         // Run test and compare output
         gameboy.srl_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let carry_flag = gameboy.cpu.flags.get_flag(Flag::C);
 
         assert_eq!(new_r1, 0b0110_1001);
@@ -4316,11 +4504,13 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0b1101_0111);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0b1101_0111);
 
         // Run test and compare output
         gameboy.swap_hl();
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         let zero_flag = gameboy.cpu.flags.get_flag(Flag::Z);
 
         assert_eq!(new_r1, 0b0111_1101);
@@ -4350,7 +4540,9 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0x9F);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0x9F);
 
         // Run test and compare output
         gameboy.bit_hl(6);
@@ -4382,12 +4574,14 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0b0101_1010);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0b0101_1010);
 
         // Run test and compare output
         gameboy.res_hl(3);
 
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         assert_eq!(new_r1, 0b0101_0010);
     }
 
@@ -4413,13 +4607,416 @@ mod tests {
         let mut gameboy = Gameboy::new();
 
         // Set up gameboy state for test
-        gameboy.write_instruction(0x0, 0b0101_1010);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xC000);
+        gameboy.write_instruction(0xC000, 0b0101_1010);
 
         // Run test and compare output
         gameboy.set_hl(7);
 
-        let new_r1 = gameboy.read_instruction(0x0);
+        let new_r1 = gameboy.read_instruction(0xC000);
         assert_eq!(new_r1, 0b1101_1010);
     }
 
+    // cycle timing tests
+    #[test]
+    fn fetch_cycles() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // nop, ld (nn), sp and bit 0, (hl)
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x00);
+        gameboy.write_instruction(0xC001, 0x08);
+        gameboy.write_instruction(0xC002, 0x00);
+        gameboy.write_instruction(0xC003, 0xD0);
+        gameboy.write_instruction(0xC004, 0xCB);
+        gameboy.write_instruction(0xC005, 0x46);
+        gameboy.cpu.register.write_u16(RegisterU16::HL, 0xD000);
+
+        // Run test and compare output
+        assert_eq!(gameboy.fetch(), 1);
+        assert_eq!(gameboy.fetch(), 5);
+        assert_eq!(gameboy.fetch(), 3);
+        assert_eq!(gameboy.cycles, 9);
+        assert_eq!(gameboy.cpu.register.pc, 0xC006);
+    }
+
+    #[test]
+    fn jr_cc_e_cycles() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x20);
+        gameboy.write_instruction(0xC001, 0x02);
+        gameboy.cpu.flags.set_flag(Flag::Z, true);
+
+        // Not taken, then taken
+        assert_eq!(gameboy.fetch(), 2);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.flags.set_flag(Flag::Z, false);
+        assert_eq!(gameboy.fetch(), 3);
+        assert_eq!(gameboy.cpu.register.pc, 0xC004);
+    }
+
+    #[test]
+    fn jp_cc_nn_cycles() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xDA);
+        gameboy.write_instruction(0xC001, 0x00);
+        gameboy.write_instruction(0xC002, 0xD0);
+        gameboy.cpu.flags.set_flag(Flag::C, false);
+
+        // Not taken, then taken
+        assert_eq!(gameboy.fetch(), 3);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.flags.set_flag(Flag::C, true);
+        assert_eq!(gameboy.fetch(), 4);
+        assert_eq!(gameboy.cpu.register.pc, 0xD000);
+    }
+
+    #[test]
+    fn call_cc_nn_cycles() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xCC);
+        gameboy.write_instruction(0xC001, 0x00);
+        gameboy.write_instruction(0xC002, 0xD0);
+        gameboy.cpu.register.sp = 0xFFFE;
+        gameboy.cpu.flags.set_flag(Flag::Z, false);
+
+        // Not taken, then taken
+        assert_eq!(gameboy.fetch(), 3);
+        assert_eq!(gameboy.cpu.register.sp, 0xFFFE);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.flags.set_flag(Flag::Z, true);
+        assert_eq!(gameboy.fetch(), 6);
+        assert_eq!(gameboy.cpu.register.pc, 0xD000);
+        assert_eq!(gameboy.cpu.register.sp, 0xFFFC);
+    }
+
+    #[test]
+    fn ret_cc_cycles() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xD0);
+        gameboy.cpu.register.sp = 0xFFFC;
+        gameboy.write_instruction(0xFFFC, 0x00);
+        gameboy.write_instruction(0xFFFD, 0xD0);
+        gameboy.cpu.flags.set_flag(Flag::C, true);
+
+        // Not taken, then taken
+        assert_eq!(gameboy.fetch(), 2);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.flags.set_flag(Flag::C, false);
+        assert_eq!(gameboy.fetch(), 5);
+        assert_eq!(gameboy.cpu.register.pc, 0xD000);
+    }
+
+    // halt tests
+    #[test]
+    fn halt_wake_ime_enabled() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x76);
+        gameboy.write_instruction(0xC001, 0x00);
+        gameboy.write_instruction(0xFFFF, 0x04);
+        gameboy.write_instruction(0xFF0F, 0x00);
+        gameboy.cpu.set_ime_state(InterruptConds::Enabled);
+
+        // Run test and compare output
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.halted, true);
+
+        // Stays halted while nothing is requested
+        assert_eq!(gameboy.fetch(), 1);
+        assert_eq!(gameboy.cpu.halted, true);
+        assert_eq!(gameboy.cpu.register.pc, 0xC001);
+
+        // Timer interrupt requested
+        gameboy.write_instruction(0xFF0F, 0x04);
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.halted, false);
+    }
+
+    #[test]
+    fn halt_wake_ime_disabled() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x76);
+        gameboy.write_instruction(0xC001, 0x3C);
+        gameboy.write_instruction(0xFFFF, 0x01);
+        gameboy.write_instruction(0xFF0F, 0x00);
+        gameboy.cpu.set_ime_state(InterruptConds::Disabled);
+
+        // Run test and compare output
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.halted, true);
+
+        // V-blank requested, execution resumes after halt without servicing it
+        gameboy.write_instruction(0xFF0F, 0x01);
+        gameboy.fetch();
+
+        assert_eq!(gameboy.cpu.halted, false);
+        assert_eq!(gameboy.cpu.register.pc, 0xC002);
+        assert_eq!(gameboy.cpu.register.read_u8(RegisterU8::A), 0x1);
+    }
+
+    #[test]
+    fn halt_bug() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x76);
+        gameboy.write_instruction(0xC001, 0x3C);
+        gameboy.write_instruction(0xFFFF, 0x01);
+        gameboy.write_instruction(0xFF0F, 0x01);
+        gameboy.cpu.set_ime_state(InterruptConds::Disabled);
+
+        // Run test and compare output
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.halted, false);
+
+        // The byte after halt is read twice
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.register.pc, 0xC001);
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.register.pc, 0xC002);
+        assert_eq!(gameboy.cpu.register.read_u8(RegisterU8::A), 0x2);
+    }
+
+    // stop tests
+    #[test]
+    fn stop() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0x10);
+        gameboy.write_instruction(0xC001, 0x00);
+        gameboy.write_instruction(0xC002, 0x3C);
+        gameboy.write_instruction(0xFF04, 0xAB);
+        gameboy.write_instruction(0xFF00, 0xCF);
+
+        // Run test and compare output
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.stopped, true);
+        assert_eq!(gameboy.cpu.register.pc, 0xC002);
+        assert_eq!(gameboy.read_instruction(0xFF04), 0x0);
+
+        // Stays stopped while no button is pressed
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.stopped, true);
+
+        // Pressing a button pulls its line low
+        gameboy.set_button(Button::Right, true);
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.stopped, false);
+        assert_eq!(gameboy.cpu.register.read_u8(RegisterU8::A), 0x1);
+    }
+
+    #[test]
+    fn stop_speed_switch() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.set_model(Model::Cgb);
+        gameboy.write_instruction(0xC000, 0x10);
+        gameboy.write_instruction(0xC001, 0x00);
+        gameboy.write_instruction(0xFF4D, 0x01);
+
+        // Run test and compare output
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.stopped, false);
+        assert_eq!(gameboy.cpu.double_speed, true);
+        assert_eq!(gameboy.read_instruction(0xFF4D), 0xFE);
+        assert_eq!(gameboy.cpu.register.pc, 0xC002);
+    }
+
+    #[test]
+    fn key1_only_on_cgb() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Run test and compare output, a DMG has nothing at KEY1
+        gameboy.write_instruction(0xFF4D, 0x01);
+        assert_eq!(gameboy.read_instruction(0xFF4D), 0xFF);
+
+        gameboy.set_model(Model::Cgb);
+        gameboy.write_instruction(0xFF4D, 0x00);
+        assert_eq!(gameboy.read_instruction(0xFF4D), 0x7E);
+        gameboy.write_instruction(0xFF4D, 0x01);
+        assert_eq!(gameboy.read_instruction(0xFF4D), 0x7F);
+    }
+
+    // reti tests
+    #[test]
+    fn reti() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.sp = 0xFFFC;
+        gameboy.write_instruction(0xFFFC, 0xFB);
+        gameboy.write_instruction(0xFFFC + 1, 0xFA);
+        gameboy.cpu.set_ime_state(InterruptConds::Disabled);
+
+        // Run test and compare output
+        gameboy.reti();
+
+        assert_eq!(gameboy.cpu.register.pc, 0xFAFB);
+        assert_eq!(gameboy.cpu.register.sp, 0xFFFE);
+        assert_eq!(gameboy.cpu.get_ime_state(), true);
+    }
+
+    #[test]
+    fn reti_nested_interrupt() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Inside a v-blank handler with a timer interrupt waiting
+        gameboy.write_instruction(0xC040, 0xD9);
+        gameboy.cpu.register.pc = 0xC040;
+        gameboy.cpu.register.sp = 0xFFFC;
+        gameboy.write_instruction(0xFFFC, 0x00);
+        gameboy.write_instruction(0xFFFC + 1, 0xC0);
+        gameboy.write_instruction(0xFFFF, 0x04);
+        gameboy.write_instruction(0xFF0F, 0x04);
+        gameboy.cpu.set_ime_state(InterruptConds::Disabled);
+
+        // Run test and compare output
+        gameboy.fetch();
+        gameboy.handle_interrupt();
+
+        assert_eq!(gameboy.cpu.register.pc, 0x50);
+        assert_eq!(gameboy.cpu.register.sp, 0xFFFC);
+        assert_eq!(gameboy.read_instruction(0xFFFC), 0x00);
+        assert_eq!(gameboy.read_instruction(0xFFFC + 1), 0xC0);
+    }
+
+    #[test]
+    fn ret_no_nested_interrupt() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Inside a v-blank handler with a timer interrupt waiting
+        gameboy.write_instruction(0xC040, 0xC9);
+        gameboy.cpu.register.pc = 0xC040;
+        gameboy.cpu.register.sp = 0xFFFC;
+        gameboy.write_instruction(0xFFFC, 0x00);
+        gameboy.write_instruction(0xFFFC + 1, 0xC0);
+        gameboy.write_instruction(0xFFFF, 0x04);
+        gameboy.write_instruction(0xFF0F, 0x04);
+        gameboy.cpu.set_ime_state(InterruptConds::Disabled);
+
+        // Run test and compare output
+        gameboy.fetch();
+        gameboy.handle_interrupt();
+
+        assert_eq!(gameboy.cpu.register.pc, 0xC000);
+        assert_eq!(gameboy.cpu.get_ime_state(), false);
+    }
+
+    // ei/di tests
+    #[test]
+    fn ei() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xFB);
+        gameboy.write_instruction(0xC001, 0x00);
+
+        // IME is only set after the instruction following ei
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.get_ime_state(), false);
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.get_ime_state(), true);
+    }
+
+    #[test]
+    fn di() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xF3);
+        gameboy.cpu.set_ime_state(InterruptConds::Enabled);
+
+        // Run test and compare output
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.get_ime_state(), false);
+    }
+
+    #[test]
+    fn ei_di() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xFB);
+        gameboy.write_instruction(0xC001, 0xF3);
+        gameboy.write_instruction(0xC002, 0x00);
+
+        // di cancels the pending enable
+        gameboy.fetch();
+        gameboy.fetch();
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.get_ime_state(), false);
+        assert_eq!(gameboy.cpu.ime_pending, false);
+    }
+
+    #[test]
+    fn ei_halt() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(0xC000, 0xFB);
+        gameboy.write_instruction(0xC001, 0x76);
+        gameboy.write_instruction(0xFFFF, 0x01);
+        gameboy.write_instruction(0xFF0F, 0x00);
+
+        // The CPU halts with interrupts enabled
+        gameboy.fetch();
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.halted, true);
+        assert_eq!(gameboy.cpu.halt_bug, false);
+        assert_eq!(gameboy.cpu.get_ime_state(), true);
+    }
+
+    #[test]
+    #[ignore = "needs Blargg's test ROMs in TEST/blargg"]
+    fn blargg_cpu_instrs() {
+        let output = run_blargg("TEST/blargg/cpu_instrs/cpu_instrs.gb");
+        assert_eq!(output.contains("Passed all tests"), true, "{}", output);
+    }
+
 }
\ No newline at end of file
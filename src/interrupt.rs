@@ -0,0 +1,253 @@
+use crate::cpu::InterruptConds;
+use crate::gameboy::Gameboy;
+
+pub const INTERRUPT_FLAG: u16 = 0xFF0F;
+pub const INTERRUPT_ENABLE: u16 = 0xFFFF;
+
+// Interrupt sources, listed from highest to lowest priority
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad
+}
+
+impl Interrupt {
+    pub const PRIORITY: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad
+    ];
+
+    // Bit in IE and IF
+    pub fn bit(&self) -> u8 {
+        match self {
+            Interrupt::VBlank => 0x01,
+            Interrupt::LcdStat => 0x02,
+            Interrupt::Timer => 0x04,
+            Interrupt::Serial => 0x08,
+            Interrupt::Joypad => 0x10
+        }
+    }
+
+    pub fn vector(&self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::LcdStat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60
+        }
+    }
+
+    // Picks the highest priority interrupt out of a set of IE & IF bits
+    pub fn highest_priority(pending: u8) -> Option<Interrupt> {
+        Interrupt::PRIORITY.into_iter().find(|interrupt| pending & interrupt.bit() != 0)
+    }
+}
+
+impl Gameboy {
+    // Interrupts that are both requested in IF and enabled in IE
+    pub fn pending_interrupts(&self) -> u8 {
+        let ie_flag = self.read_instruction(INTERRUPT_ENABLE);
+        let if_flag = self.read_instruction(INTERRUPT_FLAG);
+        ie_flag & if_flag & 0x1F
+    }
+
+    #[cfg(test)]
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.memory.request_interrupt(interrupt);
+    }
+
+    // Services at most one pending interrupt and returns the M-cycles spent dispatching it
+    pub fn handle_interrupt(&mut self) -> u8 {
+        if !self.cpu.get_ime_state() {
+            return 0;
+        }
+
+        let interrupt = match Interrupt::highest_priority(self.pending_interrupts()) {
+            Some(interrupt) => interrupt,
+            None => return 0
+        };
+
+        self.cpu.set_ime_state(InterruptConds::Disabled);
+        self.cpu.halted = false;
+
+        let if_flag = self.read_instruction(INTERRUPT_FLAG);
+        self.write_instruction(INTERRUPT_FLAG, if_flag & !interrupt.bit());
+
+        // When ei is directly followed by a bugged halt the handler returns to the halt itself
+        let mut return_pc = self.cpu.register.pc;
+        if self.cpu.halt_bug {
+            self.cpu.halt_bug = false;
+            return_pc = return_pc.wrapping_sub(1);
+        }
+
+        let [lsb_pc, msb_pc] = return_pc.to_le_bytes();
+        self.cpu.register.sp = self.cpu.register.sp.wrapping_sub(1);
+        self.write_cycle(self.cpu.register.sp, msb_pc);
+        self.cpu.register.sp = self.cpu.register.sp.wrapping_sub(1);
+        self.write_cycle(self.cpu.register.sp, lsb_pc);
+
+        self.cpu.register.pc = interrupt.vector();
+
+        5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu::{LCDC, STAT};
+
+    #[test]
+    fn priority() {
+        assert_eq!(Interrupt::highest_priority(0x00), None);
+        assert_eq!(Interrupt::highest_priority(0x1F), Some(Interrupt::VBlank));
+        assert_eq!(Interrupt::highest_priority(0x06), Some(Interrupt::LcdStat));
+        assert_eq!(Interrupt::highest_priority(0x18), Some(Interrupt::Serial));
+        assert_eq!(Interrupt::highest_priority(0x10), Some(Interrupt::Joypad));
+    }
+
+    #[test]
+    fn dispatch_one_per_check() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0x1234;
+        gameboy.cpu.register.sp = 0xFFFE;
+        gameboy.cpu.set_ime_state(InterruptConds::Enabled);
+        gameboy.write_instruction(INTERRUPT_ENABLE, 0x1F);
+        gameboy.write_instruction(INTERRUPT_FLAG, 0x18);
+
+        // Run test and compare output
+        let cycles = gameboy.handle_interrupt();
+
+        assert_eq!(cycles, 5);
+        assert_eq!(gameboy.cpu.register.pc, 0x58);
+        assert_eq!(gameboy.cpu.register.sp, 0xFFFC);
+        assert_eq!(gameboy.read_instruction(0xFFFD), 0x12);
+        assert_eq!(gameboy.read_instruction(0xFFFC), 0x34);
+        assert_eq!(gameboy.cpu.get_ime_state(), false);
+
+        // Joypad is still requested but waits for IME
+        assert_eq!(gameboy.read_instruction(INTERRUPT_FLAG), 0xF0);
+        assert_eq!(gameboy.handle_interrupt(), 0);
+        assert_eq!(gameboy.cpu.register.pc, 0x58);
+    }
+
+    #[test]
+    fn dispatch_joypad() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.sp = 0xFFFE;
+        gameboy.cpu.set_ime_state(InterruptConds::Enabled);
+        gameboy.write_instruction(INTERRUPT_ENABLE, 0x10);
+        gameboy.request_interrupt(Interrupt::Joypad);
+
+        // Run test and compare output
+        gameboy.handle_interrupt();
+        assert_eq!(gameboy.cpu.register.pc, 0x60);
+    }
+
+    #[test]
+    fn disabled_interrupt_ignored() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.cpu.register.pc = 0x1234;
+        gameboy.cpu.register.sp = 0xFFFE;
+        gameboy.cpu.set_ime_state(InterruptConds::Enabled);
+        gameboy.write_instruction(INTERRUPT_ENABLE, 0x01);
+        gameboy.request_interrupt(Interrupt::Timer);
+
+        // Run test and compare output
+        assert_eq!(gameboy.handle_interrupt(), 0);
+        assert_eq!(gameboy.cpu.register.pc, 0x1234);
+        assert_eq!(gameboy.cpu.get_ime_state(), true);
+    }
+
+    #[test]
+    fn if_upper_bits() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Run test and compare output
+        gameboy.write_instruction(INTERRUPT_FLAG, 0x00);
+        assert_eq!(gameboy.read_instruction(INTERRUPT_FLAG), 0xE0);
+    }
+
+    #[test]
+    fn fetch_services_interrupt() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.write_instruction(0xC000, 0x00);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.sp = 0xFFFE;
+        gameboy.cpu.set_ime_state(InterruptConds::Enabled);
+        gameboy.write_instruction(INTERRUPT_ENABLE, 0x04);
+        gameboy.request_interrupt(Interrupt::Timer);
+
+        // nop followed by the dispatch
+        assert_eq!(gameboy.fetch(), 6);
+        assert_eq!(gameboy.cpu.register.pc, 0x50);
+        assert_eq!(gameboy.read_instruction(0xFFFC), 0x01);
+        assert_eq!(gameboy.read_instruction(0xFFFD), 0xC0);
+    }
+
+    #[test]
+    fn stat_interrupt_from_ppu() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        for address in 0xC000..0xC100 {
+            gameboy.write_instruction(address, 0x00);
+        }
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.sp = 0xFFFE;
+        gameboy.cpu.set_ime_state(InterruptConds::Enabled);
+        gameboy.write_instruction(INTERRUPT_ENABLE, 0x02);
+        gameboy.write_instruction(STAT, 0x08);
+        gameboy.write_instruction(LCDC, 0x80);
+
+        // Run test and compare output, HBlank starts 63 M-cycles into the line
+        while gameboy.cpu.register.pc < 0xC100 && gameboy.cpu.register.pc != 0x48 {
+            gameboy.fetch();
+        }
+        assert_eq!(gameboy.cpu.register.pc, 0x48);
+        assert_eq!(gameboy.read_instruction(0xFFFC), 0x3F);
+    }
+
+    #[test]
+    fn ei_halt_bug_returns_to_halt() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.write_instruction(0xC000, 0xFB);
+        gameboy.write_instruction(0xC001, 0x76);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.cpu.register.sp = 0xFFFE;
+        gameboy.write_instruction(INTERRUPT_ENABLE, 0x01);
+        gameboy.request_interrupt(Interrupt::VBlank);
+
+        // Run test and compare output
+        gameboy.fetch();
+        gameboy.fetch();
+
+        assert_eq!(gameboy.cpu.register.pc, 0x40);
+        assert_eq!(gameboy.read_instruction(0xFFFC), 0x01);
+        assert_eq!(gameboy.read_instruction(0xFFFD), 0xC0);
+    }
+}
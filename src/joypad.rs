@@ -0,0 +1,187 @@
+use crate::mmu::IoDevice;
+
+pub const P1: u16 = 0xFF00;
+
+// Select lines in P1, a line is selected while its bit is 0
+const SELECT_DPAD: u8 = 0x10;
+const SELECT_BUTTONS: u8 = 0x20;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start
+}
+
+impl Button {
+    // Bit in `Joypad::pressed`, the d-pad is the low nibble and the buttons the high one so
+    // each nibble lines up with the P1 input lines
+    fn bit(&self) -> u8 {
+        match self {
+            Button::Right => 0x01,
+            Button::Left => 0x02,
+            Button::Up => 0x04,
+            Button::Down => 0x08,
+            Button::A => 0x10,
+            Button::B => 0x20,
+            Button::Select => 0x40,
+            Button::Start => 0x80
+        }
+    }
+}
+
+// P1. The d-pad and the buttons share the four input lines, which read 0 for a pressed key
+// in whichever matrix is selected.
+pub struct Joypad {
+    // Select bits as last written
+    select: u8,
+    pressed: u8,
+    // An input line went from high to low since the bus last checked
+    interrupt: bool,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            select: SELECT_DPAD | SELECT_BUTTONS,
+            pressed: 0x00,
+            interrupt: false,
+        }
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        let old_lines = self.input_lines();
+        if pressed {
+            self.pressed |= button.bit();
+        }
+        else {
+            self.pressed &= !button.bit();
+        }
+        self.detect_falling_edge(old_lines);
+    }
+
+    // Returns whether the joypad interrupt was requested and clears the request
+    pub fn take_interrupt(&mut self) -> bool {
+        let interrupt = self.interrupt;
+        self.interrupt = false;
+        interrupt
+    }
+
+    // Low nibble of P1, a bit is 0 while any selected key on that line is held
+    fn input_lines(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.select & SELECT_DPAD == 0 {
+            lines &= !(self.pressed & 0x0F);
+        }
+        if self.select & SELECT_BUTTONS == 0 {
+            lines &= !(self.pressed >> 4);
+        }
+        lines
+    }
+
+    fn detect_falling_edge(&mut self, old_lines: u8) {
+        if old_lines & !self.input_lines() != 0 {
+            self.interrupt = true;
+        }
+    }
+}
+
+impl IoDevice for Joypad {
+    fn read(&self, _address: u16) -> u8 {
+        0xC0 | self.select | self.input_lines()
+    }
+
+    fn write(&mut self, _address: u16, data: u8) {
+        // Selecting a matrix with a key already held pulls its line low too
+        let old_lines = self.input_lines();
+        self.select = data & (SELECT_DPAD | SELECT_BUTTONS);
+        self.detect_falling_edge(old_lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::Gameboy;
+    use crate::interrupt::INTERRUPT_FLAG;
+
+    #[test]
+    fn matrix_select() {
+        // Set up joypad for test
+        let mut joypad = Joypad::new();
+        joypad.set_button(Button::Down, true);
+        joypad.set_button(Button::A, true);
+
+        // Run test and compare output
+        assert_eq!(joypad.read(P1), 0xFF);
+        joypad.write(P1, SELECT_BUTTONS);
+        assert_eq!(joypad.read(P1), 0xE7);
+        joypad.write(P1, SELECT_DPAD);
+        assert_eq!(joypad.read(P1), 0xDE);
+        joypad.write(P1, 0x00);
+        assert_eq!(joypad.read(P1), 0xC6);
+    }
+
+    #[test]
+    fn interrupt_on_press() {
+        // Set up joypad for test
+        let mut joypad = Joypad::new();
+        joypad.write(P1, SELECT_DPAD);
+
+        // Run test and compare output, only keys in the selected matrix pull a line low
+        joypad.set_button(Button::Up, true);
+        assert_eq!(joypad.take_interrupt(), false);
+        joypad.set_button(Button::Start, true);
+        assert_eq!(joypad.take_interrupt(), true);
+        assert_eq!(joypad.take_interrupt(), false);
+
+        // Releasing is a rising edge, selecting the d-pad with Up held is a falling one
+        joypad.set_button(Button::Start, false);
+        assert_eq!(joypad.take_interrupt(), false);
+        joypad.write(P1, SELECT_BUTTONS);
+        assert_eq!(joypad.take_interrupt(), true);
+    }
+
+    #[test]
+    fn set_button_requests_interrupt() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.write_instruction(0xC000, 0x00);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(P1, SELECT_DPAD);
+
+        // Run test and compare output
+        gameboy.set_button(Button::B, true);
+        gameboy.fetch();
+        assert_eq!(gameboy.read_instruction(INTERRUPT_FLAG), 0xF0);
+        assert_eq!(gameboy.read_instruction(P1), 0xDD);
+    }
+
+    #[test]
+    fn press_wakes_from_stop() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        gameboy.write_instruction(0xC000, 0x10);
+        gameboy.write_instruction(0xC001, 0x00);
+        gameboy.write_instruction(0xC002, 0x00);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(P1, SELECT_BUTTONS);
+
+        // Run test and compare output
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.stopped, true);
+        gameboy.set_button(Button::Left, true);
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.stopped, false);
+        assert_eq!(gameboy.cpu.register.pc, 0xC003);
+    }
+}
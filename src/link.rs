@@ -0,0 +1,678 @@
+use std::cell::RefCell;
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::gameboy::Gameboy;
+use crate::serial::SerialEndpoint;
+
+// Every message is a kind, a data byte and a 64-bit little endian value
+const MESSAGE_SIZE: usize = 10;
+// A byte sent on the sender's clock and the partner's answer, both carry the transfer's
+// sequence number so a reply that turns up late can't be taken for the next one
+const TRANSFER: u8 = 0x01;
+const REPLY: u8 = 0x02;
+// The sender's emulated time in M-cycles
+const TIME: u8 = 0x03;
+
+// Neither side runs more than this many M-cycles ahead of the other, about a quarter of a frame
+const SYNC_WINDOW: u64 = 4096;
+// How often each side reports its time while it isn't waiting on the other
+const SYNC_INTERVAL: u64 = 1024;
+// A partner that doesn't answer for this long by default is taken to be paused. We carry on
+// without it and line the clocks up again once it's back, rather than freezing along with it.
+const PARTNER_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Where to find the other emulator, `unix:<path>` for a Unix-domain socket, otherwise a TCP
+// `host:port`
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkAddress {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(String),
+}
+
+impl LinkAddress {
+    pub fn parse(address: &str) -> LinkAddress {
+        #[cfg(unix)]
+        if let Some(path) = address.strip_prefix("unix:") {
+            return LinkAddress::Unix(path.to_string());
+        }
+        LinkAddress::Tcp(address.to_string())
+    }
+}
+
+impl fmt::Display for LinkAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkAddress::Tcp(address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            LinkAddress::Unix(path) => write!(f, "unix:{}", path)
+        }
+    }
+}
+
+// A connected socket to the other emulator
+pub trait LinkStream: Read + Write + Send {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl LinkStream for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl LinkStream for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+// Listens on a Unix-domain socket and removes the socket file again once dropped
+#[cfg(unix)]
+struct UnixSocketListener {
+    listener: UnixListener,
+    path: String,
+}
+
+#[cfg(unix)]
+impl UnixSocketListener {
+    fn bind(path: &str) -> io::Result<Self> {
+        remove_socket(path);
+        Ok(Self { listener: UnixListener::bind(path)?, path: path.to_string() })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        remove_socket(&self.path);
+    }
+}
+
+// Removes a socket file left behind at the path, anything else there is left alone
+#[cfg(unix)]
+fn remove_socket(path: &str) {
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// Waits for the other emulator to connect
+pub fn listen(address: &LinkAddress) -> io::Result<SocketLink> {
+    let stream: Box<dyn LinkStream> = match address {
+        LinkAddress::Tcp(address) => Box::new(tcp_stream(TcpListener::bind(address)?.accept()?.0)?),
+        #[cfg(unix)]
+        LinkAddress::Unix(path) => Box::new(UnixSocketListener::bind(path)?.listener.accept()?.0)
+    };
+    SocketLink::new(stream)
+}
+
+pub fn connect(address: &LinkAddress) -> io::Result<SocketLink> {
+    let stream: Box<dyn LinkStream> = match address {
+        LinkAddress::Tcp(address) => Box::new(tcp_stream(TcpStream::connect(address)?)?),
+        #[cfg(unix)]
+        LinkAddress::Unix(path) => Box::new(UnixStream::connect(path)?)
+    };
+    SocketLink::new(stream)
+}
+
+// Every byte is a round trip, so don't let Nagle hold them back
+fn tcp_stream(stream: TcpStream) -> io::Result<TcpStream> {
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+struct Message {
+    kind: u8,
+    data: u8,
+    value: u64,
+}
+
+impl Message {
+    fn encode(&self) -> [u8; MESSAGE_SIZE] {
+        let mut bytes = [0x00; MESSAGE_SIZE];
+        bytes[0] = self.kind;
+        bytes[1] = self.data;
+        bytes[2..].copy_from_slice(&self.value.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Message {
+        let mut value = [0x00; 8];
+        value.copy_from_slice(&bytes[2..MESSAGE_SIZE]);
+        Message { kind: bytes[0], data: bytes[1], value: u64::from_le_bytes(value) }
+    }
+}
+
+// Link cable to an emulator in another process. Both sides report their emulated time and wait
+// whenever they get more than SYNC_WINDOW M-cycles ahead, so the two clocks run in lockstep at
+// the pace of the slower one. The side clocking a transfer sends its byte and waits for the
+// partner's SB in reply, the other side answers whenever its serial port polls.
+pub struct SocketLink {
+    stream: Box<dyn LinkStream>,
+    // Bytes of a message that hasn't fully arrived yet
+    partial: Vec<u8>,
+    // Once the partner has gone the link acts as if nothing is plugged in
+    closed: bool,
+    // Sequence number of our last transfer
+    sequence: u64,
+    // A transfer the partner clocked while we were waiting for it to catch up
+    pending: Option<Message>,
+    // Our time at the last sync and the time we last told the partner
+    cycles: u64,
+    sent_cycles: u64,
+    // The partner's last reported time
+    partner_cycles: u64,
+    // Our time minus the partner's when both are level, only moves after the partner was paused
+    offset: i64,
+    // The partner stopped answering, we run on without waiting until it reports its time again
+    stalled: bool,
+    // How long the partner can stay quiet before it's taken to be paused
+    pub partner_timeout: Duration,
+}
+
+impl SocketLink {
+    pub fn new(stream: Box<dyn LinkStream>) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            partial: Vec::with_capacity(MESSAGE_SIZE),
+            closed: false,
+            sequence: 0,
+            pending: None,
+            cycles: 0,
+            sent_cycles: 0,
+            partner_cycles: 0,
+            offset: 0,
+            stalled: false,
+            partner_timeout: PARTNER_TIMEOUT,
+        })
+    }
+
+    fn close(&mut self, error: Option<io::Error>) {
+        if let Some(error) = error {
+            eprintln!("Link cable disconnected: {}", error);
+        }
+        self.closed = true;
+    }
+
+    fn send(&mut self, kind: u8, data: u8, value: u64) {
+        let bytes = Message { kind, data, value }.encode();
+        let mut message: &[u8] = &bytes;
+        while !message.is_empty() && !self.closed {
+            match self.stream.write(message) {
+                Ok(0) => self.close(None),
+                Ok(written) => message = &message[written..],
+                Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => thread::yield_now(),
+                Err(error) => self.close(Some(error))
+            }
+        }
+    }
+
+    // Returns the next message if one has arrived, without waiting for it
+    fn receive(&mut self) -> Option<Message> {
+        while !self.closed {
+            let mut buffer = [0; MESSAGE_SIZE];
+            let wanted = MESSAGE_SIZE - self.partial.len();
+            match self.stream.read(&mut buffer[..wanted]) {
+                Ok(0) => self.close(None),
+                Ok(read) => {
+                    self.partial.extend_from_slice(&buffer[..read]);
+                    if self.partial.len() == MESSAGE_SIZE {
+                        let message = Message::decode(&self.partial);
+                        self.partial.clear();
+                        return Some(message);
+                    }
+                }
+                // A blocking read that timed out reports the same as a non-blocking one
+                Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return None,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => self.close(Some(error))
+            }
+        }
+
+        None
+    }
+
+    // Like receive, but sleeps until a message arrives or the deadline passes
+    fn receive_until(&mut self, deadline: Instant) -> Option<Message> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout.is_zero() {
+            return None;
+        }
+
+        if let Err(error) = self.stream.set_nonblocking(false).and_then(|_| self.stream.set_read_timeout(Some(timeout))) {
+            self.close(Some(error));
+            return None;
+        }
+        let message = self.receive();
+        if let Err(error) = self.stream.set_nonblocking(true) {
+            self.close(Some(error));
+        }
+        message
+    }
+
+    // Like receive, but takes in time reports and only returns transfers and replies
+    fn next_message(&mut self) -> Option<Message> {
+        while let Some(message) = self.receive() {
+            if let Some(message) = self.take_time(message) {
+                return Some(message);
+            }
+        }
+
+        None
+    }
+
+    // Takes in a time report, anything else is handed back
+    fn take_time(&mut self, message: Message) -> Option<Message> {
+        if message.kind != TIME {
+            return Some(message);
+        }
+
+        self.partner_cycles = message.value;
+        if self.stalled {
+            self.offset = self.cycles as i64 - self.partner_cycles as i64;
+            self.stalled = false;
+        }
+        None
+    }
+
+    // M-cycles we're ahead of the partner, negative when behind
+    fn ahead(&self) -> i64 {
+        self.cycles as i64 - self.partner_cycles as i64 - self.offset
+    }
+
+    // Keeps a transfer for `external_transfer` to answer, anything else is a reply that arrived
+    // after its transfer gave up
+    fn keep_transfer(&mut self, message: Message) {
+        if message.kind == TRANSFER {
+            self.pending = Some(message);
+        }
+    }
+}
+
+impl SerialEndpoint for SocketLink {
+    fn transfer(&mut self, data: u8) -> u8 {
+        self.sequence = self.sequence.wrapping_add(1);
+        let sequence = self.sequence;
+        self.send(TRANSFER, data, sequence);
+
+        // The partner clocked a transfer we haven't answered yet
+        if let Some(message) = self.pending.take() {
+            self.send(REPLY, 0xFF, message.value);
+        }
+
+        let deadline = Instant::now() + self.partner_timeout;
+        while let Some(message) = self.receive_until(deadline) {
+            match self.take_time(message) {
+                Some(Message { kind: REPLY, data, value }) if value == sequence => return data,
+                // Both sides started a transfer on their own clock, neither drives the other's
+                // input so each reads 0xFF
+                Some(Message { kind: TRANSFER, value, .. }) => self.send(REPLY, 0xFF, value),
+                // A time report, or a reply to a transfer that already gave up waiting
+                _ => {}
+            }
+        }
+
+        if !self.closed {
+            eprintln!("Link partner didn't answer a transfer");
+            self.stalled = true;
+        }
+        0xFF
+    }
+
+    fn external_transfer(&mut self, data: Option<u8>) -> Option<u8> {
+        while self.pending.is_none() {
+            let message = self.next_message()?;
+            self.keep_transfer(message);
+        }
+
+        let message = self.pending.take()?;
+        self.send(REPLY, data.unwrap_or(0xFF), message.value);
+        data.map(|_| message.data)
+    }
+
+    fn sync(&mut self, cycles: u64) {
+        self.cycles = cycles;
+        while let Some(message) = self.next_message() {
+            self.keep_transfer(message);
+        }
+
+        let ahead = self.ahead() > SYNC_WINDOW as i64;
+        if ahead || cycles - self.sent_cycles >= SYNC_INTERVAL {
+            self.send(TIME, 0x00, cycles);
+            self.sent_cycles = cycles;
+        }
+        if !ahead || self.stalled {
+            return;
+        }
+
+        // Wait for the partner to catch up. A transfer it clocks meanwhile is answered straight
+        // away by the serial port's next poll, so it never waits on us in turn.
+        let deadline = Instant::now() + self.partner_timeout;
+        while self.pending.is_none() && self.ahead() > SYNC_WINDOW as i64 {
+            match self.receive_until(deadline) {
+                Some(message) => {
+                    if let Some(message) = self.take_time(message) {
+                        self.keep_transfer(message);
+                    }
+                }
+                None if self.closed => return,
+                None => {
+                    eprintln!("Link partner stopped responding, running on without it");
+                    self.stalled = true;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// What one side of an in-memory link cable has told the other
+#[derive(Default)]
+struct LinkSide {
+    // SB while waiting on the partner's clock
+    waiting: Option<u8>,
+    // Byte the partner clocked in
+    received: Option<u8>,
+}
+
+// One end of a link cable between two Gameboys in the same process
+pub struct MemoryLink {
+    sides: Rc<RefCell<[LinkSide; 2]>>,
+    side: usize,
+}
+
+pub fn memory_link() -> (MemoryLink, MemoryLink) {
+    let sides = Rc::new(RefCell::new([LinkSide::default(), LinkSide::default()]));
+    (MemoryLink { sides: sides.clone(), side: 0 }, MemoryLink { sides, side: 1 })
+}
+
+impl SerialEndpoint for MemoryLink {
+    fn transfer(&mut self, data: u8) -> u8 {
+        let partner = &mut self.sides.borrow_mut()[1 - self.side];
+        match partner.waiting.take() {
+            Some(partner_data) => {
+                partner.received = Some(data);
+                partner_data
+            }
+            None => 0xFF
+        }
+    }
+
+    fn external_transfer(&mut self, data: Option<u8>) -> Option<u8> {
+        let side = &mut self.sides.borrow_mut()[self.side];
+        side.waiting = data;
+        side.received.take().filter(|_| data.is_some())
+    }
+}
+
+// Plugs a link cable between two Gameboys, run them with `run_linked`
+pub fn connect_linked(first: &mut Gameboy, second: &mut Gameboy) {
+    let (first_end, second_end) = memory_link();
+    first.connect_serial(Box::new(first_end));
+    second.connect_serial(Box::new(second_end));
+}
+
+// Runs the first Gameboy for at least a number of M-cycles and the second up to the same point,
+// always stepping whichever is behind so neither gets more than an instruction ahead of the
+// other. Both have to have started together. Returns the M-cycles the first one ran for.
+pub fn run_linked(first: &mut Gameboy, second: &mut Gameboy, cycles: u64) -> u64 {
+    let start = first.cycles;
+    while first.cycles - start < cycles || second.cycles < first.cycles {
+        if first.cycles <= second.cycles {
+            first.fetch();
+        }
+        else {
+            second.fetch();
+        }
+    }
+
+    first.cycles - start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupt::INTERRUPT_FLAG;
+    use crate::test_roms::temp_dir;
+
+    // Loads a program into WRAM that sends a byte with the given SC value, waits for the transfer
+    // to finish and then spins. The internally clocked side waits first so the other is ready.
+    fn load_transfer(gameboy: &mut Gameboy, data: u8, control: u8) {
+        let program = [
+            0x06, 0x00, 0x05, 0x20, 0xFD,                       // ld b, 0; dec b; jr nz, -3
+            0x3E, data, 0xE0, 0x01, 0x3E, control, 0xE0, 0x02,  // ld a, data; ldh (SB), a; ld a, control; ldh (SC), a
+            0xF0, 0x02, 0x17, 0x38, 0xFB,                       // ldh a, (SC); rla; jr c, -5
+            0x18, 0xFE,                                         // jr -2
+        ];
+        for (offset, byte) in program.iter().enumerate() {
+            gameboy.write_instruction(0xC000 + offset as u16, *byte);
+        }
+        // The side on the external clock skips the delay
+        gameboy.cpu.register.pc = if control & 0x01 != 0 { 0xC000 } else { 0xC005 };
+    }
+
+    #[test]
+    fn linked_in_memory() {
+        // Create two gameboys for testing purposes
+        let mut master = Gameboy::new();
+        let mut slave = Gameboy::new();
+        connect_linked(&mut master, &mut slave);
+
+        // Set up gameboy state for test
+        load_transfer(&mut master, 0x17, 0x81);
+        load_transfer(&mut slave, 0x42, 0x80);
+
+        // Run test and compare output
+        run_linked(&mut master, &mut slave, 5000);
+        assert_eq!(master.memory.serial.sb, 0x42);
+        assert_eq!(slave.memory.serial.sb, 0x17);
+        assert_eq!(master.read_instruction(INTERRUPT_FLAG) & 0x08, 0x08);
+        assert_eq!(slave.read_instruction(INTERRUPT_FLAG) & 0x08, 0x08);
+        assert_eq!(master.cycles.abs_diff(slave.cycles) < 8, true);
+    }
+
+    #[test]
+    fn memory_link_partner_not_ready() {
+        // Set up link for test, the partner isn't waiting on our clock
+        let (mut first, mut second) = memory_link();
+        assert_eq!(second.external_transfer(None), None);
+
+        // Run test and compare output
+        assert_eq!(first.transfer(0x12), 0xFF);
+        assert_eq!(second.external_transfer(Some(0x34)), None);
+        assert_eq!(first.transfer(0x56), 0x34);
+        assert_eq!(second.external_transfer(Some(0x34)), Some(0x56));
+    }
+
+    #[test]
+    fn address_parsing() {
+        assert_eq!(LinkAddress::parse("127.0.0.1:7777"), LinkAddress::Tcp("127.0.0.1:7777".to_string()));
+        #[cfg(unix)]
+        assert_eq!(LinkAddress::parse("unix:/tmp/link"), LinkAddress::Unix("/tmp/link".to_string()));
+    }
+
+    // Answers polls on another thread until the partner has clocked a byte in
+    fn spawn_partner(mut link: SocketLink, data: u8) -> thread::JoinHandle<u8> {
+        thread::spawn(move || loop {
+            if let Some(received) = link.external_transfer(Some(data)) {
+                return received;
+            }
+            thread::yield_now();
+        })
+    }
+
+    #[test]
+    fn tcp_link() {
+        // Set up link for test over localhost
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = LinkAddress::Tcp(listener.local_addr().unwrap().to_string());
+        let partner = thread::spawn(move || SocketLink::new(Box::new(listener.accept().unwrap().0)).unwrap());
+        let mut link = connect(&address).unwrap();
+        let partner = spawn_partner(partner.join().unwrap(), 0x42);
+
+        // Run test and compare output
+        assert_eq!(link.transfer(0x17), 0x42);
+        assert_eq!(partner.join().unwrap(), 0x17);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_link() {
+        // Set up link for test
+        let (first, second) = UnixStream::pair().unwrap();
+        let mut link = SocketLink::new(Box::new(first)).unwrap();
+        let partner = spawn_partner(SocketLink::new(Box::new(second)).unwrap(), 0x99);
+
+        // Run test and compare output
+        assert_eq!(link.transfer(0xA5), 0x99);
+        assert_eq!(partner.join().unwrap(), 0xA5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listen_again_on_unix_socket() {
+        // Set up link for test, a socket file left behind by an earlier run
+        let path = temp_dir("link_listen").join("gb.sock");
+        let address = LinkAddress::Unix(path.to_str().unwrap().to_string());
+        drop(UnixListener::bind(&path).unwrap());
+
+        // Run test and compare output, each listen replaces the stale file and removes its own
+        for _ in 0..2 {
+            let partner_address = address.clone();
+            let partner = thread::spawn(move || loop {
+                if let Ok(link) = connect(&partner_address) {
+                    return link;
+                }
+                thread::yield_now();
+            });
+            listen(&address).unwrap();
+            partner.join().unwrap();
+            assert_eq!(path.exists(), false);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn both_sides_clocking() {
+        // Set up link for test, both ends start a transfer at once
+        let (first, second) = UnixStream::pair().unwrap();
+        let mut link = SocketLink::new(Box::new(first)).unwrap();
+        let mut other = SocketLink::new(Box::new(second)).unwrap();
+        let partner = thread::spawn(move || other.transfer(0x22));
+
+        // Run test and compare output
+        assert_eq!(link.transfer(0x11), 0xFF);
+        assert_eq!(partner.join().unwrap(), 0xFF);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn late_reply_dropped() {
+        // Set up link for test, the partner's end is driven by hand
+        let (first, mut second) = UnixStream::pair().unwrap();
+        let mut link = SocketLink::new(Box::new(first)).unwrap();
+        second.write_all(&Message { kind: REPLY, data: 0x99, value: 7 }.encode()).unwrap();
+        second.write_all(&Message { kind: REPLY, data: 0x42, value: 1 }.encode()).unwrap();
+
+        // Run test and compare output, only the reply to this transfer's sequence number counts
+        assert_eq!(link.transfer(0x11), 0x42);
+        let mut sent = [0x00; MESSAGE_SIZE];
+        second.read_exact(&mut sent).unwrap();
+        assert_eq!(sent, Message { kind: TRANSFER, data: 0x11, value: 1 }.encode());
+    }
+
+    // Two ends of a link joined by a Unix-domain socket
+    #[cfg(unix)]
+    fn socket_pair() -> (SocketLink, SocketLink) {
+        let (first, second) = UnixStream::pair().unwrap();
+        (SocketLink::new(Box::new(first)).unwrap(), SocketLink::new(Box::new(second)).unwrap())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn waits_for_partner_to_catch_up() {
+        // Set up link for test, the partner reports its time a little later
+        let (mut link, mut other) = socket_pair();
+        let partner = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            other.sync(8000);
+        });
+
+        // Run test and compare output, more than SYNC_WINDOW ahead waits for the partner
+        let start = Instant::now();
+        link.sync(10000);
+        assert_eq!(start.elapsed() >= Duration::from_millis(40), true);
+        assert_eq!(link.ahead(), 2000);
+        assert_eq!(link.stalled, false);
+        partner.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn answers_transfer_while_waiting() {
+        // Set up link for test, the partner clocks a transfer instead of reporting its time
+        let (mut link, mut other) = socket_pair();
+        let partner = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            other.transfer(0x22)
+        });
+
+        // Run test and compare output
+        link.sync(10000);
+        assert_eq!(link.external_transfer(Some(0x11)), Some(0x22));
+        assert_eq!(partner.join().unwrap(), 0x11);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_on_without_paused_partner() {
+        // Set up link for test, the partner says nothing for longer than the timeout
+        let (mut link, mut other) = socket_pair();
+        link.partner_timeout = Duration::from_millis(5);
+
+        // Run test and compare output, the clocks line up again once the partner is back
+        link.sync(10000);
+        assert_eq!(link.stalled, true);
+        other.sync(2000);
+        link.sync(10100);
+        assert_eq!(link.stalled, false);
+        assert_eq!(link.ahead(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn partner_hangs_up() {
+        // Set up link for test
+        let (first, second) = UnixStream::pair().unwrap();
+        let mut link = SocketLink::new(Box::new(first)).unwrap();
+        drop(second);
+
+        // Run test and compare output
+        assert_eq!(link.transfer(0x11), 0xFF);
+        assert_eq!(link.closed, true);
+        assert_eq!(link.external_transfer(Some(0x11)), None);
+    }
+}
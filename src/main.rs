@@ -1,59 +1,355 @@
-use std::io::{stdout, Write};
+// Tests compare flags against literal bools to mirror the instruction tables
+#![allow(clippy::bool_assert_comparison, clippy::upper_case_acronyms)]
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
 use std::path::Path;
-use std::fs;
-use std::thread::sleep;
-use std::time::Duration;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
+mod apu;
+mod boot;
 mod mmu;
 mod cpu;
+mod dma;
 mod gameboy;
+mod interrupt;
+mod joypad;
+mod link;
+mod timer;
+mod cartridge;
+mod mbc;
+mod save;
+mod serial;
+mod ppu;
+mod printer;
+mod frontend;
+mod wav;
+#[cfg(test)]
+mod test_roms;
 
+use boot::Model;
+use cartridge::Cartridge;
+use frontend::Emulation;
 use gameboy::Gameboy;
+use joypad::Button;
+use link::{LinkAddress, SocketLink};
+use ppu::Renderer;
+use printer::Printer;
+use save::BatterySave;
+use serial::StdoutEndpoint;
+use wav::AudioRecorder;
+
+const USAGE: &str = "usage: gb_emulator [--boot <boot rom>] [--model dmg|mgb|sgb|cgb] [--renderer scanline|fifo] [--headless] [--frames <n>]
+                  [--wav <file> [--stems]]
+                  [--link-listen <address> | --link-connect <address> | --link-local <rom> | --printer <dir>] <rom>
+       gb_emulator info <rom>";
+
+// Settings for running a ROM
+struct RunOptions {
+    // Without a boot ROM the emulator starts in the post-boot state
+    boot_rom: Option<String>,
+    model: Model,
+    renderer: Renderer,
+    // Run without opening a window, test ROMs that report over serial don't need one
+    headless: bool,
+    // Stop a headless run after this many frames, needs --headless or --wav
+    frames: Option<u64>,
+    // Record the audio to a WAV file, implies headless
+    wav: Option<String>,
+    // Also record every channel into its own WAV file next to the mix
+    stems: bool,
+    // Link cable to another emulator or to a second game in this one
+    link: Option<LinkMode>,
+    // Plug in a Game Boy Printer that saves its prints to this directory
+    printer: Option<String>,
+}
+
+enum LinkMode {
+    // Wait for the other emulator to connect
+    Listen(LinkAddress),
+    Connect(LinkAddress),
+    // Run this ROM alongside, it isn't shown and gets no input
+    Local(String),
+}
+
+// M-cycles in one frame, also the limit for a frame while the LCD is off
+const CYCLES_PER_FRAME: u64 = 17556;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let ["info", path] = args.iter().map(String::as_str).collect::<Vec<&str>>().as_slice() {
+        info(path);
+        return;
+    }
+
+    let mut options = RunOptions {
+        boot_rom: None,
+        model: Model::Dmg,
+        renderer: Renderer::Scanline,
+        headless: false,
+        frames: None,
+        wav: None,
+        stems: false,
+        link: None,
+        printer: None,
+    };
+    let mut rom_path = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--boot" => options.boot_rom = Some(args.next().unwrap_or_else(|| usage())),
+            "--model" => {
+                options.model = match args.next().as_deref() {
+                    Some("dmg") => Model::Dmg,
+                    Some("mgb") => Model::Mgb,
+                    Some("sgb") => Model::Sgb,
+                    Some("cgb") => Model::Cgb,
+                    _ => usage()
+                }
+            }
+            "--renderer" => {
+                options.renderer = match args.next().as_deref() {
+                    Some("scanline") => Renderer::Scanline,
+                    Some("fifo") => Renderer::Fifo,
+                    _ => usage()
+                }
+            }
+            "--headless" => options.headless = true,
+            "--frames" => {
+                let frames = args.next().and_then(|frames| frames.parse().ok());
+                options.frames = Some(frames.unwrap_or_else(|| usage()));
+            }
+            "--wav" => options.wav = Some(args.next().unwrap_or_else(|| usage())),
+            "--stems" => options.stems = true,
+            "--link-listen" | "--link-connect" | "--link-local" if options.link.is_none() => {
+                let value = args.next().unwrap_or_else(|| usage());
+                options.link = Some(match arg.as_str() {
+                    "--link-listen" => LinkMode::Listen(LinkAddress::parse(&value)),
+                    "--link-connect" => LinkMode::Connect(LinkAddress::parse(&value)),
+                    _ => LinkMode::Local(value)
+                });
+            }
+            "--printer" => options.printer = Some(args.next().unwrap_or_else(|| usage())),
+            _ if rom_path.is_none() && !arg.starts_with("--") => rom_path = Some(arg),
+            _ => usage()
+        }
+    }
+
+    // Frames are only counted without a window
+    let headless = options.headless || options.wav.is_some();
+    if (options.frames.is_some() && !headless)
+        || (options.stems && options.wav.is_none())
+        || (options.link.is_some() && options.printer.is_some())
+    {
+        usage();
+    }
+
+    match rom_path {
+        Some(path) => run(&path, &options),
+        None => usage()
+    }
+}
 
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn load_cartridge(path: &str) -> Cartridge {
+    let rom = fs::read(path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+
+    Cartridge::from_bytes(rom).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    })
+}
+
+// Prints the parsed cartridge header
+fn info(path: &str) {
+    let cartridge = load_cartridge(path);
+
+    println!("{}", cartridge.header);
+    if !cartridge.global_checksum_valid() {
+        println!("warning: global checksum does not match the ROM contents");
+    }
+}
+
+// Sets up a Gameboy with the ROM and its save loaded and booted as the options ask
+fn load_gameboy(path: &str, options: &RunOptions) -> (Gameboy, Option<BatterySave>) {
     let mut gameboy = Gameboy::new();
+    gameboy.set_model(options.model);
+    gameboy.memory.ppu.renderer = options.renderer;
+    let mut cartridge = load_cartridge(path);
 
-    let boot_path = Path::new("./BOOT/dmg_boot.bin");
-    let boot_rom = fs::read(boot_path).expect("File not found!");
+    let save = cartridge.has_battery().then(|| BatterySave::for_rom(Path::new(path)));
+    if let Some(save) = &save {
+        if let Err(error) = save.load(&mut cartridge) {
+            eprintln!("{}: {}", save.path().display(), error);
+            process::exit(1);
+        }
+    }
+    gameboy.memory.load_cartridge(cartridge);
 
-    let cart_path = Path::new("./BOOT/Tetris (World) (Rev 1).gb");
-    let cart = fs::read(cart_path).expect("File not found!");
+    match &options.boot_rom {
+        Some(boot_path) => {
+            let boot_rom = fs::read(boot_path).unwrap_or_else(|error| {
+                eprintln!("{}: {}", boot_path, error);
+                process::exit(1);
+            });
+            gameboy.load_boot_rom(boot_rom);
+        }
+        None => gameboy.skip_boot()
+    }
 
-    let test_path = Path::new("./TEST/cpu_instr/03-op sp,hl.gb");
-    let test = fs::read(test_path).expect("File not found!");
+    (gameboy, save)
+}
 
-    //gameboy.memory.copy_to_ram(0, &cart);
-    //gameboy.memory.copy_to_ram(0, &boot_rom);
-    gameboy.memory.copy_to_ram(0, &test);
-    //gameboy.write_instruction(0xFF44, 0x90);
+fn run(path: &str, options: &RunOptions) {
+    let (mut gameboy, save) = load_gameboy(path, options);
 
-    // for val in boot_rom.iter() {
-    //     //println!("{:#X}", val)
-    //     memory.copy_to_ram(0, &boot_rom);
-    // }
+    let mut partner = None;
+    match (&options.link, &options.printer) {
+        (Some(LinkMode::Local(partner_path)), _) => {
+            let (mut partner_gameboy, partner_save) = load_gameboy(partner_path, options);
+            link::connect_linked(&mut gameboy, &mut partner_gameboy);
+            partner = Some((partner_gameboy, partner_save));
+        }
+        (Some(LinkMode::Listen(address)), _) => {
+            println!("Waiting for the other emulator on {}", address);
+            connect_socket(&mut gameboy, address, link::listen(address));
+        }
+        (Some(LinkMode::Connect(address)), _) => connect_socket(&mut gameboy, address, link::connect(address)),
+        (None, Some(printer_dir)) => gameboy.connect_serial(Box::new(Printer::new(Path::new(printer_dir)))),
+        // Blargg test ROMs report their results over the serial port
+        (None, None) => gameboy.connect_serial(Box::new(StdoutEndpoint))
+    }
 
-    // //Checking if the data has been loaded in correctly
-    // for (i, item) in gameboy.memory.ram.iter().enumerate() {
-    //     println!("{:#X}: {}", i, item);
-    // }
+    // Ctrl-C stops the emulator so the save file gets its final flush
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst))
+        .expect("Failed to install Ctrl-C handler");
 
-    // let mut counter = 1;
-    loop {
-        if gameboy.memory.ram[0xFF02] == 0x81 {
-            let c = gameboy.memory.ram[0xFF01];
-            print!("{}", c as char);
-            //stdout().flush();
-            //sleep(Duration::from_millis(10));
-            gameboy.memory.ram[0xFF02] = 0x0;
+    let recorder = options.wav.as_ref().map(|wav_path| {
+        if options.stems {
+            gameboy.memory.apu.enable_stems();
         }
+        let sample_rate = gameboy.memory.apu.sample_rate();
+        AudioRecorder::create(Path::new(wav_path), sample_rate, options.stems).unwrap_or_else(|error| {
+            eprintln!("{}: {}", wav_path, error);
+            process::exit(1);
+        })
+    });
 
-        // println!("{}", counter);
-        gameboy.fetch();
-        // counter += 1;
-        // if counter > 47932 {
-        //     gameboy.memory.copy_to_ram(0, &cart);
-        // }
+    let mut emulator = Emulator { gameboy, save, partner, recorder, running };
+    if options.headless || options.wav.is_some() {
+        let mut frames = 0;
+        while options.frames.is_none_or(|limit| frames < limit) && emulator.run_frame() {
+            frames += 1;
+        }
+        emulator.shutdown();
+    }
+    else {
+        frontend::run_window(emulator);
+    }
+}
+
+fn connect_socket(gameboy: &mut Gameboy, address: &LinkAddress, link: io::Result<SocketLink>) {
+    let link = link.unwrap_or_else(|error| {
+        eprintln!("{}: {}", address, error);
+        process::exit(1);
+    });
+    gameboy.connect_serial(Box::new(link));
+}
+
+// A running game along with the host side state that goes with it
+struct Emulator {
+    gameboy: Gameboy,
+    save: Option<BatterySave>,
+    // Second game on the other end of the link cable, kept level with the first
+    partner: Option<(Gameboy, Option<BatterySave>)>,
+    recorder: Option<AudioRecorder<BufWriter<File>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl Emulation for Emulator {
+    fn run_frame(&mut self) -> bool {
+        let gameboy = &mut self.gameboy;
+        let mut frame_cycles = 0;
+
+        while frame_cycles < CYCLES_PER_FRAME && !gameboy.memory.ppu.frame_ready {
+            let cycles = match self.partner.as_mut() {
+                Some((partner, partner_save)) => {
+                    let cycles = link::run_linked(gameboy, partner, 1);
+                    update_save(partner_save, partner, cycles);
+                    cycles
+                }
+                None => gameboy.fetch() as u64
+            };
+            frame_cycles += cycles;
+            update_save(&mut self.save, gameboy, cycles);
+        }
+        gameboy.memory.ppu.frame_ready = false;
+        if let Some((partner, _)) = self.partner.as_mut() {
+            partner.memory.ppu.frame_ready = false;
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(error) = recorder.record(&mut gameboy.memory.apu) {
+                eprintln!("Failed to write audio: {}", error);
+                return false;
+            }
+        }
+
+        self.running.load(Ordering::SeqCst)
+    }
+
+    fn framebuffer(&self) -> &[u8] {
+        &self.gameboy.memory.ppu.framebuffer
     }
 
+    fn set_button(&mut self, button: Button, pressed: bool) {
+        self.gameboy.set_button(button, pressed);
+    }
+
+    fn rumble(&self) -> bool {
+        self.gameboy.rumble()
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(error) = recorder.finish() {
+                eprintln!("Failed to write audio: {}", error);
+            }
+        }
+
+        flush_save(&mut self.save, &mut self.gameboy);
+        if let Some((partner, partner_save)) = self.partner.as_mut() {
+            flush_save(partner_save, partner);
+        }
+    }
+}
+
+// Counts the cycles towards the save's next periodic flush
+fn update_save(save: &mut Option<BatterySave>, gameboy: &mut Gameboy, cycles: u64) {
+    if let (Some(save), Some(cartridge)) = (save.as_mut(), gameboy.memory.cartridge.as_mut()) {
+        if let Err(error) = save.update(cartridge, cycles) {
+            eprintln!("{}: {}", save.path().display(), error);
+        }
+    }
+}
+
+fn flush_save(save: &mut Option<BatterySave>, gameboy: &mut Gameboy) {
+    if let (Some(save), Some(cartridge)) = (save.as_mut(), gameboy.memory.cartridge.as_mut()) {
+        if let Err(error) = save.flush(cartridge) {
+            eprintln!("{}: {}", save.path().display(), error);
+        }
+    }
 }
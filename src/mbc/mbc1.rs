@@ -0,0 +1,267 @@
+use super::{ram_offset, read_rom_bank, Mbc};
+
+pub struct Mbc1 {
+    // 0x0000 - 0x1FFF, RAM is only accessible after writing 0x0A
+    ram_enabled: bool,
+    // 0x2000 - 0x3FFF, low five bits of the ROM bank
+    bank1: u8,
+    // 0x4000 - 0x5FFF, two more bits used for the upper ROM bank or the RAM bank
+    bank2: u8,
+    // 0x6000 - 0x7FFF, when set BANK2 also applies to 0x0000 - 0x3FFF and RAM
+    mode: bool,
+    // MBC1M multicarts leave bit 4 of BANK1 unconnected and wire BANK2 one bit lower
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(multicart: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            bank1: 0x01,
+            bank2: 0x00,
+            mode: false,
+            multicart,
+        }
+    }
+
+    // Multicarts are 8 Mbit and repeat the Nintendo logo at the start of each 2 Mbit game
+    pub fn is_multicart(rom: &[u8]) -> bool {
+        rom.len() == 0x100000 && rom[0x40104..0x40134] == rom[0x104..0x134]
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        }
+        else {
+            5
+        }
+    }
+
+    fn rom_bank_low(&self) -> usize {
+        if self.mode {
+            (self.bank2 << self.bank2_shift()) as usize
+        }
+        else {
+            0
+        }
+    }
+
+    fn rom_bank_high(&self) -> usize {
+        let bank1 = if self.multicart {
+            self.bank1 & 0x0F
+        }
+        else {
+            self.bank1
+        };
+        ((self.bank2 << self.bank2_shift()) | bank1) as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.mode {
+            self.bank2 as usize
+        }
+        else {
+            0
+        }
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(rom, self.rom_bank_low(), address),
+            _ => read_rom_bank(rom, self.rom_bank_high(), address)
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            // Bank 0 can't be selected here, so 0x00/0x20/0x40/0x60 select the bank above
+            0x2000..=0x3FFF => {
+                self.bank1 = data & 0x1F;
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = data & 0x03,
+            _ => self.mode = data & 0x01 == 0x01
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        ram_offset(ram, self.ram_bank(), address).map_or(0xFF, |offset| ram[offset])
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(offset) = ram_offset(ram, self.ram_bank(), address) {
+            ram[offset] = data;
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::ROM_BANK_SIZE;
+    use crate::test_roms::run_mooneye;
+
+    // ROM where the first byte of every bank holds its bank number
+    fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0x00; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom
+    }
+
+    #[test]
+    fn bank_zero_selects_one() {
+        // Set up mbc for test
+        let rom = numbered_rom(8);
+        let mut mbc = Mbc1::new(false);
+
+        // Run test and compare output
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x01);
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x05);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x00);
+    }
+
+    #[test]
+    fn bank_number_wraps_to_rom_size() {
+        // Set up mbc for test
+        let rom = numbered_rom(4);
+        let mut mbc = Mbc1::new(false);
+
+        // Run test and compare output
+        mbc.write_rom(0x2000, 0x06);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x02);
+    }
+
+    #[test]
+    fn banks_20_40_60() {
+        // Set up mbc for test
+        let rom = numbered_rom(128);
+        let mut mbc = Mbc1::new(false);
+
+        // Run test and compare output
+        for (bank2, expected) in [(1, 0x21), (2, 0x41), (3, 0x61)] {
+            mbc.write_rom(0x4000, bank2);
+            mbc.write_rom(0x2000, 0x00);
+            assert_eq!(mbc.read_rom(&rom, 0x4000), expected);
+        }
+    }
+
+    #[test]
+    fn mode_select_banks_low_rom() {
+        // Set up mbc for test
+        let rom = numbered_rom(128);
+        let mut mbc = Mbc1::new(false);
+        mbc.write_rom(0x4000, 0x02);
+
+        // Run test and compare output
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x40);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x41);
+    }
+
+    #[test]
+    fn ram_enable() {
+        // Set up mbc for test
+        let mut ram = vec![0x00; 0x2000];
+        let mut mbc = Mbc1::new(false);
+
+        // Run test and compare output
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+        assert_eq!(ram[0], 0x00);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x12);
+
+        // Only the low nibble is decoded
+        mbc.write_rom(0x1FFF, 0xFA);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x12);
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+
+    #[test]
+    fn ram_banking() {
+        // Set up mbc for test
+        let mut ram = vec![0x00; 0x8000];
+        let mut mbc = Mbc1::new(false);
+        mbc.write_rom(0x0000, 0x0A);
+
+        // Run test and compare output
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(&mut ram, 0xA000, 0x11);
+        assert_eq!(ram[0x0000], 0x11);
+
+        mbc.write_rom(0x6000, 0x01);
+        mbc.write_ram(&mut ram, 0xA000, 0x22);
+        assert_eq!(ram[0x4000], 0x22);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x22);
+    }
+
+    #[test]
+    fn multicart() {
+        // Set up mbc for test
+        let rom = numbered_rom(64);
+        let mut mbc = Mbc1::new(true);
+
+        // Run test and compare output
+        mbc.write_rom(0x4000, 0x01);
+        mbc.write_rom(0x2000, 0x12);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x12);
+
+        // Bit 4 of BANK1 is not connected, so 0x10 passes the zero check but maps to bank 0
+        mbc.write_rom(0x2000, 0x10);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x10);
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x00);
+
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x30);
+    }
+
+    #[test]
+    fn multicart_detection() {
+        // Set up rom for test
+        let mut rom = vec![0x00; 0x100000];
+        rom[0x104..0x134].copy_from_slice(&[0xCE; 0x30]);
+
+        // Run test and compare output
+        assert_eq!(Mbc1::is_multicart(&rom), false);
+        rom[0x40104..0x40134].copy_from_slice(&[0xCE; 0x30]);
+        assert_eq!(Mbc1::is_multicart(&rom), true);
+    }
+
+    #[test]
+    #[ignore = "needs the mooneye test suite in TEST/mooneye"]
+    fn mooneye_mbc1() {
+        for name in [
+            "bits_bank1", "bits_bank2", "bits_mode", "bits_ramg", "multicart_rom_8Mb", "ram_64kb",
+            "ram_256kb", "rom_512kb", "rom_1Mb", "rom_2Mb", "rom_4Mb", "rom_8Mb", "rom_16Mb"
+        ] {
+            let path = format!("TEST/mooneye/emulator-only/mbc1/{}.gb", name);
+            assert_eq!(run_mooneye(&path), true, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use super::{ram_offset, read_rom_bank, Mbc};
+
+// Size of the RAM built into the MBC2 chip, 512 half-bytes
+pub const MBC2_RAM_SIZE: usize = 0x200;
+
+pub struct Mbc2 {
+    ram_enabled: bool,
+    // Four bit ROM bank where 0 selects 1
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x01,
+        }
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(rom, 0, address),
+            _ => read_rom_bank(rom, self.rom_bank as usize, address)
+        }
+    }
+
+    // Both registers live in 0x0000 - 0x3FFF, address bit 8 picks which one is written
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x3FFF if address & 0x0100 == 0 => self.ram_enabled = data & 0x0F == 0x0A,
+            0x0000..=0x3FFF => {
+                self.rom_bank = data & 0x0F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Only the low nibble is stored, the upper four data lines float high. The 512 bytes repeat
+    // across the whole of 0xA000 - 0xBFFF.
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        ram_offset(ram, 0, address).map_or(0xFF, |offset| ram[offset] | 0xF0)
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(offset) = ram_offset(ram, 0, address) {
+            ram[offset] = data & 0x0F;
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::ROM_BANK_SIZE;
+
+    #[test]
+    fn register_select() {
+        // Set up mbc for test
+        let mut rom = vec![0x00; 16 * ROM_BANK_SIZE];
+        for bank in 0..16 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let mut ram = vec![0x00; MBC2_RAM_SIZE];
+        let mut mbc = Mbc2::new();
+
+        // Run test and compare output
+        mbc.write_rom(0x2100, 0x05);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x05);
+        mbc.write_rom(0x0100, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x01);
+
+        // Bit 8 clear goes to RAM enable and leaves the bank alone
+        mbc.write_rom(0x2000, 0x0A);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x01);
+        mbc.write_ram(&mut ram, 0xA000, 0x05);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xF5);
+    }
+
+    #[test]
+    fn half_byte_ram() {
+        // Set up mbc for test
+        let mut ram = vec![0x00; MBC2_RAM_SIZE];
+        let mut mbc = Mbc2::new();
+        mbc.write_rom(0x0000, 0x0A);
+
+        // Run test and compare output
+        mbc.write_ram(&mut ram, 0xA010, 0xAB);
+        assert_eq!(ram[0x10], 0x0B);
+        assert_eq!(mbc.read_ram(&ram, 0xA010), 0xFB);
+
+        // Mirrored every 512 bytes
+        assert_eq!(mbc.read_ram(&ram, 0xA210), 0xFB);
+        assert_eq!(mbc.read_ram(&ram, 0xBE10), 0xFB);
+        mbc.write_ram(&mut ram, 0xBFFF, 0x03);
+        assert_eq!(ram[0x1FF], 0x03);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA010), 0xFF);
+    }
+}
@@ -0,0 +1,172 @@
+use super::rtc::{Rtc, RTC_FOOTER_SIZE};
+use super::{ram_offset, read_rom_bank, Mbc};
+
+pub struct Mbc3 {
+    // 0x0000 - 0x1FFF, gates both RAM and the RTC registers
+    ram_enabled: bool,
+    // 0x2000 - 0x3FFF, seven bit ROM bank where 0 selects 1
+    rom_bank: u8,
+    // 0x4000 - 0x5FFF, 0x00 - 0x07 selects a RAM bank and 0x08 - 0x0C an RTC register
+    ram_select: u8,
+    // Only fitted on MBC3+TIMER cartridges
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(rtc: Option<Rtc>) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x01,
+            ram_select: 0x00,
+            rtc,
+        }
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(rom, 0, address),
+            _ => read_rom_bank(rom, self.rom_bank as usize, address)
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = data & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_select = data & 0x0F,
+            _ => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write_latch(data);
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        match (self.ram_select, self.rtc.as_ref()) {
+            (0x00..=0x07, _) => ram_offset(ram, self.ram_select as usize, address).map_or(0xFF, |offset| ram[offset]),
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_select),
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        match (self.ram_select, self.rtc.as_mut()) {
+            (0x00..=0x07, _) => {
+                if let Some(offset) = ram_offset(ram, self.ram_select as usize, address) {
+                    ram[offset] = data;
+                }
+            }
+            (0x08..=0x0C, Some(rtc)) => rtc.write(self.ram_select, data),
+            _ => {}
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_footer(&self) -> Option<Vec<u8>> {
+        self.rtc.as_ref().map(|rtc| rtc.footer().to_vec())
+    }
+
+    fn load_save_footer(&mut self, footer: &[u8]) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            if footer.len() >= RTC_FOOTER_SIZE {
+                rtc.load_footer(footer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::rtc::tests::FakeClock;
+    use crate::mbc::ROM_BANK_SIZE;
+
+    #[test]
+    fn rom_banking() {
+        // Set up mbc for test
+        let mut rom = vec![0x00; 128 * ROM_BANK_SIZE];
+        for bank in 0..128 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let mut mbc = Mbc3::new(None);
+
+        // Run test and compare output
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x01);
+        mbc.write_rom(0x2000, 0x7F);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x7F);
+        mbc.write_rom(0x2000, 0xA0);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x20);
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x00);
+    }
+
+    #[test]
+    fn ram_banking() {
+        // Set up mbc for test
+        let mut ram = vec![0x00; 0x8000];
+        let mut mbc = Mbc3::new(None);
+
+        // Run test and compare output
+        mbc.write_ram(&mut ram, 0xA000, 0x11);
+        assert_eq!(ram[0x0000], 0x00);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_ram(&mut ram, 0xA001, 0x22);
+        assert_eq!(ram[0x6001], 0x22);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0x22);
+    }
+
+    #[test]
+    fn rtc_registers() {
+        // Set up mbc for test
+        let clock = FakeClock::new(0);
+        let mut ram = vec![0x00; 0x2000];
+        let mut mbc = Mbc3::new(Some(Rtc::new(Box::new(clock.clone()))));
+        mbc.write_rom(0x0000, 0x0A);
+
+        // Run test and compare output
+        mbc.write_rom(0x4000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 5);
+        clock.advance(3600);
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 6);
+        assert_eq!(ram[0], 0x00);
+
+        // Switching back to a RAM bank leaves the clock alone
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x00);
+    }
+
+    #[test]
+    fn rtc_absent() {
+        // Set up mbc for test
+        let mut ram = vec![0x00; 0x2000];
+        let mut mbc = Mbc3::new(None);
+        mbc.write_rom(0x0000, 0x0A);
+
+        // Run test and compare output
+        mbc.write_rom(0x4000, 0x08);
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+        assert_eq!(mbc.save_footer(), None);
+    }
+}
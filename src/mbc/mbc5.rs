@@ -0,0 +1,142 @@
+use super::{ram_offset, read_rom_bank, Mbc};
+
+pub struct Mbc5 {
+    // 0x0000 - 0x1FFF, unlike older mappers the whole byte has to be 0x0A
+    ram_enabled: bool,
+    // 0x2000 - 0x2FFF low eight bits, 0x3000 - 0x3FFF bit 8. Bank 0 can be selected here.
+    rom_bank: u16,
+    // 0x4000 - 0x5FFF
+    ram_bank: u8,
+    // Rumble cartridges wire bit 3 of the RAM bank register to the motor instead of RAM
+    has_rumble: bool,
+    rumble: bool,
+}
+
+impl Mbc5 {
+    pub fn new(has_rumble: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            has_rumble,
+            rumble: false,
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(rom, 0, address),
+            _ => read_rom_bank(rom, self.rom_bank as usize, address)
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = data == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | data as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((data as u16 & 0x01) << 8),
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    self.rumble = data & 0x08 != 0;
+                    self.ram_bank = data & 0x07;
+                }
+                else {
+                    self.ram_bank = data & 0x0F;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        ram_offset(ram, self.ram_bank as usize, address).map_or(0xFF, |offset| ram[offset])
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(offset) = ram_offset(ram, self.ram_bank as usize, address) {
+            ram[offset] = data;
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::ROM_BANK_SIZE;
+
+    #[test]
+    fn nine_bit_rom_bank() {
+        // Set up mbc for test, 8 MiB with the bank number in the first two bytes of each bank
+        let mut rom = vec![0x00; 512 * ROM_BANK_SIZE];
+        for bank in 0..512 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        let mut mbc = Mbc5::new(false);
+
+        // Run test and compare output
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x01);
+        mbc.write_rom(0x2000, 0xFF);
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0xFF);
+        assert_eq!(mbc.read_rom(&rom, 0x4001), 0x01);
+
+        // Bank 0 is not remapped to bank 1
+        mbc.write_rom(0x2000, 0x00);
+        mbc.write_rom(0x3000, 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x00);
+        assert_eq!(mbc.read_rom(&rom, 0x4001), 0x00);
+    }
+
+    #[test]
+    fn ram_banking() {
+        // Set up mbc for test
+        let mut ram = vec![0x00; 0x20000];
+        let mut mbc = Mbc5::new(false);
+
+        // Run test and compare output
+        mbc.write_rom(0x0000, 0x1A);
+        mbc.write_ram(&mut ram, 0xA000, 0x11);
+        assert_eq!(ram[0], 0x00);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x0F);
+        mbc.write_ram(&mut ram, 0xA000, 0x22);
+        assert_eq!(ram[0x1E000], 0x22);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x22);
+        assert_eq!(mbc.rumble(), false);
+    }
+
+    #[test]
+    fn rumble_motor() {
+        // Set up mbc for test
+        let mut ram = vec![0x00; 0x8000];
+        let mut mbc = Mbc5::new(true);
+        mbc.write_rom(0x0000, 0x0A);
+
+        // Run test and compare output
+        mbc.write_rom(0x4000, 0x09);
+        assert_eq!(mbc.rumble(), true);
+        mbc.write_ram(&mut ram, 0xA000, 0x33);
+        assert_eq!(ram[0x2000], 0x33);
+
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.rumble(), false);
+    }
+}
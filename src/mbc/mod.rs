@@ -0,0 +1,59 @@
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod rom_only;
+pub mod rtc;
+
+pub use mbc1::Mbc1;
+pub use mbc2::{Mbc2, MBC2_RAM_SIZE};
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use rom_only::RomOnly;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+// Memory bank controller on the cartridge. The cartridge owns the ROM and RAM contents and
+// lends them to the controller, which decides which bank each CPU address lands in.
+pub trait Mbc {
+    // 0x0000 - 0x7FFF
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8;
+    // Writes to ROM space drive the controller's registers
+    fn write_rom(&mut self, address: u16, data: u8);
+    // 0xA000 - 0xBFFF
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8;
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8);
+    // Whether the enable register currently lets the CPU at RAM
+    fn ram_enabled(&self) -> bool;
+
+    // Extra state stored after the RAM in the save file, like the MBC3 clock
+    fn save_footer(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn load_save_footer(&mut self, _footer: &[u8]) {}
+
+    // Whether a rumble cartridge currently has its motor switched on
+    fn rumble(&self) -> bool {
+        false
+    }
+}
+
+// Reads a byte from a ROM bank, wrapping the bank number to the banks actually present
+pub fn read_rom_bank(rom: &[u8], bank: usize, address: u16) -> u8 {
+    if rom.is_empty() {
+        return 0xFF;
+    }
+    let banks = rom.len().div_ceil(ROM_BANK_SIZE);
+    let offset = (bank % banks) * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+    rom.get(offset).copied().unwrap_or(0xFF)
+}
+
+// Offset into cartridge RAM for a bank, wrapping to the RAM actually fitted
+pub fn ram_offset(ram: &[u8], bank: usize, address: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+    Some((bank * RAM_BANK_SIZE + (address - 0xA000) as usize) % ram.len())
+}
@@ -0,0 +1,26 @@
+use super::{ram_offset, Mbc};
+
+// 32 KiB of ROM wired straight to the bus, optionally with up to 8 KiB of RAM
+pub struct RomOnly;
+
+impl Mbc for RomOnly {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _data: u8) {}
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        ram_offset(ram, 0, address).map_or(0xFF, |offset| ram[offset])
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) {
+        if let Some(offset) = ram_offset(ram, 0, address) {
+            ram[offset] = data;
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,351 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Size of the RTC state appended after the save RAM, shared with BGB, VBA-M and SameBoy
+pub const RTC_FOOTER_SIZE: usize = 48;
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+// Source of wall clock time for the RTC, in seconds since the Unix epoch
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+    }
+}
+
+// MBC3 real-time clock. Rather than counting every second the clock catches up with the host
+// time whenever the game touches it.
+pub struct Rtc {
+    clock: Box<dyn Clock>,
+    // Host time the registers were last brought up to date
+    last_sync: u64,
+    // Live registers, 0x08 - 0x0C
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    // Bit 0 - 7 of the 9-bit day counter
+    days_low: u8,
+    // Bit 0 is day bit 8, bit 6 halts the clock and bit 7 is set when the day counter overflows
+    days_high: u8,
+    // Copy of the registers taken by the latch, this is what the CPU reads
+    latched: [u8; 5],
+    // 0x6000 - 0x7FFF, writing 0x00 then 0x01 latches
+    latch_armed: bool,
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        let last_sync = clock.now();
+        Self {
+            clock,
+            last_sync,
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days_low: 0,
+            days_high: 0,
+            latched: [0x00; 5],
+            latch_armed: false,
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.days_high & 0x40 != 0
+    }
+
+    fn days(&self) -> u16 {
+        ((self.days_high as u16 & 0x01) << 8) | self.days_low as u16
+    }
+
+    fn set_days(&mut self, days: u16) {
+        self.days_low = days as u8;
+        self.days_high = (self.days_high & 0xFE) | ((days >> 8) as u8 & 0x01);
+    }
+
+    // Brings the live registers up to the current host time
+    fn sync(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_sync);
+        self.last_sync = now;
+        if !self.halted() {
+            self.advance(elapsed);
+        }
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        // Registers written out of range count up to their bit width and wrap without carrying,
+        // step those one second at a time until the clock is back in range
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick_second();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let total = self.seconds as u64
+            + self.minutes as u64 * 60
+            + self.hours as u64 * 3600
+            + self.days() as u64 * SECONDS_PER_DAY
+            + seconds;
+        let days = total / SECONDS_PER_DAY;
+        if days > 0x1FF {
+            self.days_high |= 0x80;
+        }
+
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        self.set_days((days & 0x1FF) as u16);
+    }
+
+    fn tick_second(&mut self) {
+        if self.seconds == 59 {
+            self.seconds = 0;
+        }
+        else {
+            self.seconds = (self.seconds + 1) & 0x3F;
+            return;
+        }
+
+        if self.minutes == 59 {
+            self.minutes = 0;
+        }
+        else {
+            self.minutes = (self.minutes + 1) & 0x3F;
+            return;
+        }
+
+        if self.hours == 23 {
+            self.hours = 0;
+        }
+        else {
+            self.hours = (self.hours + 1) & 0x1F;
+            return;
+        }
+
+        if self.days() == 0x1FF {
+            self.set_days(0);
+            self.days_high |= 0x80;
+        }
+        else {
+            self.set_days(self.days() + 1);
+        }
+    }
+
+    fn registers(&self) -> [u8; 5] {
+        [self.seconds, self.minutes, self.hours, self.days_low, self.days_high]
+    }
+
+    pub fn write_latch(&mut self, data: u8) {
+        if self.latch_armed && data == 0x01 {
+            self.sync();
+            self.latched = self.registers();
+        }
+        self.latch_armed = data == 0x00;
+    }
+
+    // Register select 0x08 - 0x0C
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched[(register - 0x08) as usize]
+    }
+
+    pub fn write(&mut self, register: u8, data: u8) {
+        self.sync();
+        match register {
+            0x08 => self.seconds = data & 0x3F,
+            0x09 => self.minutes = data & 0x3F,
+            0x0A => self.hours = data & 0x1F,
+            0x0B => self.days_low = data,
+            _ => self.days_high = data & 0xC1
+        }
+        // Reads only see the latched copy, keep it in step so a write reads back without relatching
+        self.latched[(register - 0x08) as usize] = self.registers()[(register - 0x08) as usize];
+    }
+
+    // Live registers, latched registers and the host time they were saved at, all little endian
+    pub fn footer(&self) -> [u8; RTC_FOOTER_SIZE] {
+        let mut footer = [0x00; RTC_FOOTER_SIZE];
+        for (i, value) in self.registers().iter().chain(self.latched.iter()).enumerate() {
+            footer[i * 4..i * 4 + 4].copy_from_slice(&(*value as u32).to_le_bytes());
+        }
+        footer[40..48].copy_from_slice(&self.last_sync.to_le_bytes());
+        footer
+    }
+
+    pub fn load_footer(&mut self, footer: &[u8]) {
+        if footer.len() < RTC_FOOTER_SIZE {
+            return;
+        }
+        let field = |i: usize| footer[i * 4];
+        self.seconds = field(0) & 0x3F;
+        self.minutes = field(1) & 0x3F;
+        self.hours = field(2) & 0x1F;
+        self.days_low = field(3);
+        self.days_high = field(4) & 0xC1;
+        for i in 0..5 {
+            self.latched[i] = field(5 + i);
+        }
+        let mut timestamp = [0x00; 8];
+        timestamp.copy_from_slice(&footer[40..48]);
+        self.last_sync = u64::from_le_bytes(timestamp);
+        // Catch up on the time that passed while the emulator was closed
+        self.sync();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // Clock the test moves forward by hand
+    #[derive(Clone)]
+    pub struct FakeClock(pub Rc<Cell<u64>>);
+
+    impl FakeClock {
+        pub fn new(now: u64) -> Self {
+            FakeClock(Rc::new(Cell::new(now)))
+        }
+
+        pub fn advance(&self, seconds: u64) {
+            self.0.set(self.0.get() + seconds);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    fn latch(rtc: &mut Rtc) {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+    }
+
+    #[test]
+    fn counts_host_time() {
+        // Set up rtc for test
+        let clock = FakeClock::new(1000);
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+
+        // Run test and compare output
+        clock.advance(SECONDS_PER_DAY + 3600 + 60 + 1);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 1);
+        assert_eq!(rtc.read(0x09), 1);
+        assert_eq!(rtc.read(0x0A), 1);
+        assert_eq!(rtc.read(0x0B), 1);
+        assert_eq!(rtc.read(0x0C), 0);
+    }
+
+    #[test]
+    fn latch_holds_value() {
+        // Set up rtc for test
+        let clock = FakeClock::new(0);
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+
+        // Run test and compare output
+        clock.advance(5);
+        latch(&mut rtc);
+        clock.advance(5);
+        assert_eq!(rtc.read(0x08), 5);
+
+        // Latching needs 0x00 written first
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(0x08), 5);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 10);
+    }
+
+    #[test]
+    fn halt_stops_clock() {
+        // Set up rtc for test
+        let clock = FakeClock::new(0);
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+
+        // Run test and compare output
+        clock.advance(3);
+        rtc.write(0x0C, 0x40);
+        clock.advance(100);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 3);
+
+        rtc.write(0x0C, 0x00);
+        clock.advance(2);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 5);
+    }
+
+    #[test]
+    fn day_carry() {
+        // Set up rtc for test
+        let clock = FakeClock::new(0);
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+        rtc.write(0x0B, 0xFF);
+        rtc.write(0x0C, 0x01);
+        rtc.write(0x0A, 23);
+        rtc.write(0x09, 59);
+        rtc.write(0x08, 59);
+
+        // Run test and compare output
+        clock.advance(1);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x0B), 0x00);
+        assert_eq!(rtc.read(0x0C), 0x80);
+
+        // The carry stays set until the game clears it
+        clock.advance(SECONDS_PER_DAY);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x0B), 0x01);
+        assert_eq!(rtc.read(0x0C), 0x80);
+        rtc.write(0x0C, 0x00);
+        assert_eq!(rtc.read(0x0C), 0x00);
+    }
+
+    #[test]
+    fn out_of_range_wraps_without_carry() {
+        // Set up rtc for test
+        let clock = FakeClock::new(0);
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+        rtc.write(0x08, 0x3F);
+
+        // Run test and compare output
+        clock.advance(1);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 0x00);
+        assert_eq!(rtc.read(0x09), 0x00);
+    }
+
+    #[test]
+    fn footer_round_trip() {
+        // Set up rtc for test
+        let clock = FakeClock::new(1_700_000_000);
+        let mut rtc = Rtc::new(Box::new(clock.clone()));
+        rtc.write(0x0A, 12);
+        clock.advance(30);
+        latch(&mut rtc);
+        let footer = rtc.footer();
+
+        // Run test and compare output
+        assert_eq!(footer[8], 12);
+        assert_eq!(footer[0], 30);
+        assert_eq!(u64::from_le_bytes(footer[40..48].try_into().unwrap()), 1_700_000_030);
+
+        // Time spent powered off is caught up on load
+        clock.advance(3600);
+        let mut restored = Rtc::new(Box::new(clock.clone()));
+        restored.load_footer(&footer);
+        assert_eq!(restored.read(0x0A), 12);
+        latch(&mut restored);
+        assert_eq!(restored.read(0x0A), 13);
+        assert_eq!(restored.read(0x08), 30);
+    }
+}
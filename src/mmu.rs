@@ -1,23 +1,223 @@
+use crate::apu::{Apu, NR10, WAVE_RAM_END};
+use crate::boot::BOOT_ROM_DISABLE;
+use crate::cartridge::Cartridge;
+use crate::dma::{OamDma, DMA};
+use crate::joypad::{Joypad, P1};
+use crate::interrupt::{Interrupt, INTERRUPT_ENABLE, INTERRUPT_FLAG};
+use crate::ppu::{Ppu, BGP, LCDC, LYC, WX};
+use crate::serial::{Serial, SB, SC};
+use crate::timer::{Timer, DIV, TAC};
+
+// A peripheral mapped into the I/O register range. Each device decides how its registers
+// behave, e.g. bits that always read back as 1 or registers with side effects on write.
+pub trait IoDevice {
+    fn read(&self, address: u16) -> u8;
+    fn write(&mut self, address: u16, data: u8);
+}
+
+// I/O registers that have no dedicated device yet, stored as plain bytes
+pub struct IoRegisters {
+    pub registers: [u8; 0x80],
+    // Registers only the CGB has, such as KEY1, are connected
+    pub cgb: bool,
+}
+
+impl IoRegisters {
+    pub fn new() -> Self {
+        Self {
+            registers: [0x00; 0x80],
+            cgb: false,
+        }
+    }
+
+    // Bits that are not connected and always read back as 1
+    fn unused_bits(&self, address: u16) -> u8 {
+        match address {
+            0xFF4D if self.cgb => 0x7E,
+            0xFF03 | 0xFF08..=0xFF0E | 0xFF4C..=0xFF7F => 0xFF,
+            _ => 0x00
+        }
+    }
+}
+
+impl IoDevice for IoRegisters {
+    fn read(&self, address: u16) -> u8 {
+        self.registers[(address - 0xFF00) as usize] | self.unused_bits(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        self.registers[(address - 0xFF00) as usize] = data;
+    }
+}
+
+// Routes CPU reads and writes to whichever part of the hardware owns the address
 pub struct MemoryBus {
-    pub ram: [u8; 0x10000 as usize],
+    // Overlays 0x0000 - 0x00FF, and 0x0200 - 0x08FF for the larger CGB boot ROM, until FF50 is written
+    pub boot_rom: Option<Vec<u8>>,
+    // 0x0000 - 0x7FFF and 0xA000 - 0xBFFF, None while the slot is empty
+    pub cartridge: Option<Cartridge>,
+    // 0xC000 - 0xDFFF, mirrored at 0xE000 - 0xFDFF
+    pub wram: [u8; 0x2000],
+    // 0xFF00 - 0xFF7F
+    pub io: IoRegisters,
+    // 0xFF80 - 0xFFFE
+    pub hram: [u8; 0x7F],
+    pub interrupt_enable: u8,
+    pub interrupt_flag: u8,
+    pub timer: Timer,
+    // Owns VRAM and OAM as well as the LCD registers
+    pub ppu: Ppu,
+    // Copies into OAM and takes the bus from the CPU while it runs
+    pub dma: OamDma,
+    pub joypad: Joypad,
+    pub apu: Apu,
+    pub serial: Serial,
 }
 
 impl MemoryBus {
     pub fn new() -> Self {
         Self {
-            ram: [0xFF; 0x10000 as usize],
+            boot_rom: None,
+            cartridge: None,
+            wram: [0x00; 0x2000],
+            io: IoRegisters::new(),
+            hram: [0x00; 0x7F],
+            // No interrupts are enabled or requested at power on
+            interrupt_enable: 0x00,
+            interrupt_flag: 0x00,
+            timer: Timer::new(),
+            ppu: Ppu::new(),
+            dma: OamDma::new(),
+            joypad: Joypad::new(),
+            apu: Apu::new(),
+            serial: Serial::new(),
         }
     }
 
-    pub fn copy_to_ram(&mut self, address: u16, rom_file: &[u8]) {
-        self.ram[(address as usize)..(address as usize + rom_file.len())].copy_from_slice(rom_file);
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) {
+        self.cartridge = Some(cartridge);
     }
 
+    // Reads on behalf of the CPU, which loses the bus to a running OAM DMA
     pub fn read_byte(&self, address: u16) -> u8 {
-        self.ram[address as usize]
+        if self.dma.blocks(address) {
+            return 0xFF;
+        }
+        self.read_bus(address)
+    }
+
+    fn read_bus(&self, address: u16) -> u8 {
+        if let Some(data) = self.boot_rom_byte(address) {
+            return data;
+        }
+
+        match address {
+            // An empty slot leaves the data bus floating high
+            0x0000..=0x7FFF => self.cartridge.as_ref().map_or(0xFF, |cartridge| cartridge.read_rom(address)),
+            0x8000..=0x9FFF => self.ppu.vram[(address - 0x8000) as usize],
+            0xA000..=0xBFFF => self.cartridge.as_ref().map_or(0xFF, |cartridge| cartridge.read_ram(address)),
+            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize],
+            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize],
+            0xFE00..=0xFE9F => self.ppu.oam[(address - 0xFE00) as usize],
+            // Unusable region reads back as 0 on DMG
+            0xFEA0..=0xFEFF => 0x00,
+            // Only the low five bits of IF exist, the rest read back as 1
+            INTERRUPT_FLAG => self.interrupt_flag | 0xE0,
+            BOOT_ROM_DISABLE => 0xFF,
+            0xFF00..=0xFF7F => self.io_device(address).read(address),
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize],
+            INTERRUPT_ENABLE => self.interrupt_enable,
+        }
     }
 
     pub fn write_byte(&mut self, address: u16, data: u8) {
-        self.ram[address as usize] = data;
+        if self.dma.blocks(address) {
+            return;
+        }
+
+        match address {
+            0x0000..=0x7FFF => {
+                if let Some(cartridge) = self.cartridge.as_mut() {
+                    cartridge.write_rom(address, data);
+                }
+            }
+            0x8000..=0x9FFF => self.ppu.vram[(address - 0x8000) as usize] = data,
+            0xA000..=0xBFFF => {
+                if let Some(cartridge) = self.cartridge.as_mut() {
+                    cartridge.write_ram(address, data);
+                }
+            }
+            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize] = data,
+            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize] = data,
+            0xFE00..=0xFE9F => self.ppu.oam[(address - 0xFE00) as usize] = data,
+            0xFEA0..=0xFEFF => {},
+            INTERRUPT_FLAG => self.interrupt_flag = data & 0x1F,
+            BOOT_ROM_DISABLE => self.boot_rom = None,
+            0xFF00..=0xFF7F => self.io_device_mut(address).write(address, data),
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize] = data,
+            INTERRUPT_ENABLE => self.interrupt_enable = data,
+        }
+    }
+
+    fn boot_rom_byte(&self, address: u16) -> Option<u8> {
+        let boot_rom = self.boot_rom.as_ref()?;
+        match address {
+            // The cartridge header stays visible between the two halves of the CGB boot ROM
+            0x0000..=0x00FF | 0x0200..=0x08FF => boot_rom.get(address as usize).copied(),
+            _ => None
+        }
+    }
+
+    // Devices register the I/O addresses they respond to here
+    fn io_device(&self, address: u16) -> &dyn IoDevice {
+        match address {
+            P1 => &self.joypad,
+            SB..=SC => &self.serial,
+            DIV..=TAC => &self.timer,
+            NR10..=WAVE_RAM_END => &self.apu,
+            LCDC..=LYC | BGP..=WX => &self.ppu,
+            DMA => &self.dma,
+            _ => &self.io
+        }
     }
-}
\ No newline at end of file
+
+    fn io_device_mut(&mut self, address: u16) -> &mut dyn IoDevice {
+        match address {
+            P1 => &mut self.joypad,
+            SB..=SC => &mut self.serial,
+            DIV..=TAC => &mut self.timer,
+            NR10..=WAVE_RAM_END => &mut self.apu,
+            LCDC..=LYC | BGP..=WX => &mut self.ppu,
+            DMA => &mut self.dma,
+            _ => &mut self.io
+        }
+    }
+
+    // Advances the hardware clocked alongside the CPU by a number of M-cycles
+    pub fn tick(&mut self, cycles: u8) {
+        if self.joypad.take_interrupt() {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+        let system_counter = self.timer.counter;
+        if self.timer.tick(cycles) {
+            self.request_interrupt(Interrupt::Timer);
+        }
+        if self.serial.tick(cycles) {
+            self.request_interrupt(Interrupt::Serial);
+        }
+        self.interrupt_flag |= self.ppu.tick(cycles);
+        self.apu.tick(cycles, system_counter);
+
+        for _ in 0..cycles {
+            if let Some((source, index)) = self.dma.step() {
+                // Sources above WRAM read from its echo instead
+                let source = if source >= 0xE000 { source - 0x2000 } else { source };
+                self.ppu.oam[index] = self.read_bus(source);
+            }
+        }
+    }
+
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt_flag |= interrupt.bit();
+    }
+}
@@ -0,0 +1,466 @@
+use std::collections::VecDeque;
+
+use super::scanline::{palette_shade, tile_pixel, Sprite, OBJ_BG_PRIORITY, OBJ_PALETTE};
+use super::*;
+
+// Dots spent on the tile fetch at the start of every line whose result is thrown away
+const STARTUP_DOTS: u8 = 6;
+// Dots the fetcher spends reading a sprite's tile number and both bitplanes
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+// Background fetcher steps, each takes two dots apart from Push which retries every dot
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FetcherStep {
+    Tile,
+    DataLow,
+    DataHigh,
+    Push
+}
+
+#[derive(Copy, Clone)]
+struct ObjPixel {
+    colour: u8,
+    palette: bool,
+    bg_priority: bool,
+}
+
+// State of the pixel pipeline for the line being drawn
+pub struct Fifo {
+    bg_fifo: VecDeque<u8>,
+    obj_fifo: VecDeque<ObjPixel>,
+    step: FetcherStep,
+    // Dot within the current step
+    step_dot: u8,
+    // Tile column the fetcher is working on, counted from the start of the line or window
+    fetcher_x: u8,
+    tile: u8,
+    data_low: u8,
+    data_high: u8,
+    // Dots left of the discarded first fetch
+    startup: u8,
+    // Background pixels still to drop for SCX fine scroll
+    discard: u8,
+    // Pixels sent to the LCD so far on this line
+    lcd_x: u8,
+    fetching_window: bool,
+    window_drawn: bool,
+    sprites: Vec<Sprite>,
+    // Sprite being fetched and how many dots of its fetch are left
+    sprite_fetch: Option<(Sprite, u8)>,
+    // Background tile the last sprite fetch waited on, the window's tiles are offset by 0x100
+    penalised_tile: Option<u16>,
+}
+
+impl Fifo {
+    pub fn new() -> Self {
+        Self {
+            bg_fifo: VecDeque::with_capacity(16),
+            obj_fifo: VecDeque::with_capacity(8),
+            step: FetcherStep::Tile,
+            step_dot: 0,
+            fetcher_x: 0,
+            tile: 0,
+            data_low: 0,
+            data_high: 0,
+            startup: 0,
+            discard: 0,
+            lcd_x: 0,
+            fetching_window: false,
+            window_drawn: false,
+            sprites: Vec::new(),
+            sprite_fetch: None,
+            penalised_tile: None,
+        }
+    }
+
+    fn restart_fetcher(&mut self) {
+        self.step = FetcherStep::Tile;
+        self.step_dot = 0;
+        self.fetcher_x = 0;
+    }
+}
+
+impl Ppu {
+    // Resets the pipeline at the start of mode 3
+    pub fn start_fifo_line(&mut self) {
+        let mut sprites = self.sprites_on_line();
+        sprites.sort_by_key(|sprite| (sprite.x, sprite.index));
+
+        let fifo = &mut self.fifo;
+        fifo.bg_fifo.clear();
+        fifo.obj_fifo.clear();
+        fifo.restart_fetcher();
+        fifo.startup = STARTUP_DOTS;
+        fifo.discard = self.scx % 8;
+        fifo.lcd_x = 0;
+        fifo.fetching_window = false;
+        fifo.window_drawn = false;
+        fifo.sprites = sprites;
+        fifo.sprite_fetch = None;
+        fifo.penalised_tile = None;
+    }
+
+    // Runs the pipeline for one dot, returns true once all 160 pixels of the line are out
+    pub fn fifo_step(&mut self) -> bool {
+        if self.fifo.startup > 0 {
+            self.fifo.startup -= 1;
+            return false;
+        }
+
+        // A sprite at the current position stalls the pipeline while it is fetched
+        if self.fifo.sprite_fetch.is_none() && self.lcdc & OBJ_ENABLE != 0 && self.fifo.discard == 0 {
+            let position = self.fifo.lcd_x as u16 + 8;
+            if let Some(index) = self.fifo.sprites.iter().position(|sprite| sprite.x as u16 <= position) {
+                let sprite = self.fifo.sprites.remove(index);
+                let penalty = self.sprite_penalty();
+                self.fifo.sprite_fetch = Some((sprite, penalty));
+            }
+        }
+
+        if let Some((sprite, dots_left)) = self.fifo.sprite_fetch {
+            if dots_left > 1 {
+                self.fifo.sprite_fetch = Some((sprite, dots_left - 1));
+                return false;
+            }
+            self.fifo.sprite_fetch = None;
+            self.load_sprite(&sprite);
+            return false;
+        }
+
+        if !self.fifo.fetching_window && self.window_visible_at(self.fifo.lcd_x as usize) && self.fifo.discard == 0 {
+            // The window replaces the background from here on, the fetcher starts again on it
+            self.fifo.fetching_window = true;
+            self.fifo.window_drawn = true;
+            self.fifo.bg_fifo.clear();
+            self.fifo.restart_fetcher();
+        }
+
+        self.step_fetcher();
+
+        if self.fifo.bg_fifo.is_empty() {
+            return false;
+        }
+
+        let bg_colour = self.fifo.bg_fifo.pop_front().unwrap_or(0);
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return false;
+        }
+
+        let obj_pixel = self.fifo.obj_fifo.pop_front();
+        self.output_pixel(bg_colour, obj_pixel);
+        self.fifo.lcd_x += 1;
+
+        if self.fifo.lcd_x as usize == SCREEN_WIDTH {
+            if self.fifo.window_drawn {
+                self.window_line += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    // Dots a sprite fetch at the current position stalls for. The background fetcher first has to
+    // finish the tile under the sprite, which only costs anything the first time that tile is hit.
+    fn sprite_penalty(&mut self) -> u8 {
+        let (tile_x, tile) = if self.fifo.fetching_window {
+            let x = (self.fifo.lcd_x as u16 + 7).saturating_sub(self.wx as u16);
+            ((x % 8) as u8, 0x100 + x / 8)
+        }
+        else {
+            let x = self.fifo.lcd_x as u16 + self.scx as u16;
+            ((x % 8) as u8, x / 8)
+        };
+
+        let wait = if self.fifo.penalised_tile == Some(tile) {
+            0
+        }
+        else {
+            (7 - tile_x).saturating_sub(2)
+        };
+        self.fifo.penalised_tile = Some(tile);
+
+        wait + SPRITE_FETCH_DOTS
+    }
+
+    fn output_pixel(&mut self, bg_colour: u8, obj_pixel: Option<ObjPixel>) {
+        // On DMG clearing LCDC bit 0 blanks both the background and the window
+        let bg_colour = if self.lcdc & BG_ENABLE != 0 { bg_colour } else { 0 };
+        let mut shade = palette_shade(self.bgp, bg_colour);
+
+        if let Some(obj) = obj_pixel {
+            let hidden = obj.bg_priority && bg_colour != 0;
+            if obj.colour != 0 && self.lcdc & OBJ_ENABLE != 0 && !hidden {
+                let palette = if obj.palette { self.obp1 } else { self.obp0 };
+                shade = palette_shade(palette, obj.colour);
+            }
+        }
+
+        let index = self.ly as usize * SCREEN_WIDTH + self.fifo.lcd_x as usize;
+        self.framebuffer[index] = shade;
+    }
+
+    fn step_fetcher(&mut self) {
+        let fifo = &mut self.fifo;
+        fifo.step_dot += 1;
+
+        match fifo.step {
+            FetcherStep::Push => {
+                // Background pixels are only pushed into an empty FIFO
+                if fifo.bg_fifo.is_empty() {
+                    for bit in (0..8).rev() {
+                        fifo.bg_fifo.push_back(tile_pixel(fifo.data_low, fifo.data_high, bit));
+                    }
+                    fifo.fetcher_x = fifo.fetcher_x.wrapping_add(1);
+                    fifo.step = FetcherStep::Tile;
+                    fifo.step_dot = 0;
+                }
+                return;
+            }
+            _ if fifo.step_dot < 2 => return,
+            _ => fifo.step_dot = 0
+        }
+
+        // Registers are read as each step completes, so mid-line writes land on the next tile
+        let (map_x, map_y, high_map) = if self.fifo.fetching_window {
+            (self.fifo.fetcher_x, self.window_line, self.lcdc & WINDOW_TILE_MAP != 0)
+        }
+        else {
+            let x = (self.scx / 8).wrapping_add(self.fifo.fetcher_x) & 0x1F;
+            (x, self.ly.wrapping_add(self.scy), self.lcdc & BG_TILE_MAP != 0)
+        };
+
+        match self.fifo.step {
+            FetcherStep::Tile => {
+                let map = if high_map { 0x1C00 } else { 0x1800 };
+                self.fifo.tile = self.vram[map + (map_y as usize / 8) * 32 + (map_x as usize & 0x1F)];
+                self.fifo.step = FetcherStep::DataLow;
+            }
+            FetcherStep::DataLow => {
+                let address = self.tile_address(self.fifo.tile) + (map_y as usize % 8) * 2;
+                self.fifo.data_low = self.vram[address];
+                self.fifo.step = FetcherStep::DataHigh;
+            }
+            FetcherStep::DataHigh => {
+                let address = self.tile_address(self.fifo.tile) + (map_y as usize % 8) * 2;
+                self.fifo.data_high = self.vram[address + 1];
+                self.fifo.step = FetcherStep::Push;
+            }
+            FetcherStep::Push => {}
+        }
+    }
+
+    // Mixes a fetched sprite into the sprite FIFO, sprites already there keep their pixels
+    fn load_sprite(&mut self, sprite: &Sprite) {
+        // Sprites hanging off the left edge lose the columns that are off screen
+        let skip = (self.fifo.lcd_x as u16 + 8).saturating_sub(sprite.x as u16) as u8;
+
+        for column in skip..8 {
+            let pixel = ObjPixel {
+                colour: self.sprite_colour(sprite, column),
+                palette: sprite.attributes & OBJ_PALETTE != 0,
+                bg_priority: sprite.attributes & OBJ_BG_PRIORITY != 0,
+            };
+            let slot = (column - skip) as usize;
+            match self.fifo.obj_fifo.get_mut(slot) {
+                Some(existing) if existing.colour == 0 => *existing = pixel,
+                Some(_) => {}
+                None => self.fifo.obj_fifo.push_back(pixel)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::Gameboy;
+    use crate::test_roms::run_screenshot;
+
+    // Runs a full visible line through the FIFO and returns how many dots mode 3 took
+    fn mode_3_length(ppu: &mut Ppu) -> u16 {
+        ppu.start_fifo_line();
+        let mut dots = 1;
+        while !ppu.fifo_step() {
+            dots += 1;
+        }
+        dots
+    }
+
+    fn ppu_for_test() -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.lcdc = LCD_ENABLE | TILE_DATA | BG_ENABLE;
+        ppu.bgp = 0xE4;
+        ppu.obp0 = 0xE4;
+        ppu
+    }
+
+    #[test]
+    fn minimum_mode_3_length() {
+        // Set up ppu for test
+        let mut ppu = ppu_for_test();
+
+        // Run test and compare output
+        assert_eq!(mode_3_length(&mut ppu), 172);
+    }
+
+    #[test]
+    fn fine_scroll_penalty() {
+        // Set up ppu for test
+        let mut ppu = ppu_for_test();
+        ppu.scx = 3;
+
+        // Run test and compare output
+        assert_eq!(mode_3_length(&mut ppu), 175);
+    }
+
+    #[test]
+    fn window_penalty() {
+        // Set up ppu for test
+        let mut ppu = ppu_for_test();
+        ppu.lcdc |= WINDOW_ENABLE;
+        ppu.window_y_triggered = true;
+        ppu.wx = 7 + 80;
+
+        // Run test and compare output
+        assert_eq!(mode_3_length(&mut ppu), 178);
+        assert_eq!(ppu.window_line, 1);
+    }
+
+    #[test]
+    fn sprite_penalty() {
+        // Set up ppu for test, a sprite lined up with the left edge of a tile waits the longest
+        let mut ppu = ppu_for_test();
+        ppu.lcdc |= OBJ_ENABLE;
+        ppu.oam[0..4].copy_from_slice(&[16, 8 + 80, 0, 0]);
+
+        // Run test and compare output
+        assert_eq!(mode_3_length(&mut ppu), 172 + 11);
+
+        // Near the right edge of a tile only the sprite fetch itself costs anything
+        ppu.oam[1] = 8 + 85;
+        assert_eq!(mode_3_length(&mut ppu), 172 + 6);
+
+        // A second sprite in the same tile doesn't wait for the background again
+        ppu.oam[1] = 8 + 80;
+        ppu.oam[4..8].copy_from_slice(&[16, 8 + 82, 0, 0]);
+        assert_eq!(mode_3_length(&mut ppu), 172 + 11 + 6);
+    }
+
+    #[test]
+    fn matches_scanline_renderer() {
+        // Set up ppu for test with a background, window and sprites
+        let mut ppu = ppu_for_test();
+        ppu.lcdc |= OBJ_ENABLE | WINDOW_ENABLE | WINDOW_TILE_MAP;
+        ppu.obp1 = 0x1B;
+        for (i, byte) in ppu.vram[16..64].iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(37);
+        }
+        for (i, tile) in ppu.vram[0x1800..0x1C00].iter_mut().enumerate() {
+            *tile = (i % 4) as u8;
+        }
+        ppu.vram[0x1C00] = 2;
+        ppu.scx = 13;
+        ppu.scy = 5;
+        ppu.wx = 100;
+        ppu.window_y_triggered = true;
+        ppu.oam[0..4].copy_from_slice(&[16, 5, 1, 0]);
+        ppu.oam[4..8].copy_from_slice(&[14, 40, 2, OBJ_PALETTE]);
+        ppu.oam[8..12].copy_from_slice(&[16, 44, 3, OBJ_BG_PRIORITY]);
+
+        // Run test and compare output
+        let mut scanline = ppu_for_test();
+        scanline.vram = ppu.vram;
+        scanline.oam = ppu.oam;
+        scanline.lcdc = ppu.lcdc;
+        scanline.obp1 = ppu.obp1;
+        scanline.scx = ppu.scx;
+        scanline.scy = ppu.scy;
+        scanline.wx = ppu.wx;
+        scanline.window_y_triggered = true;
+
+        for line in 0..8 {
+            ppu.ly = line;
+            scanline.ly = line;
+            mode_3_length(&mut ppu);
+            scanline.render_scanline();
+        }
+        assert_eq!(ppu.framebuffer[..8 * SCREEN_WIDTH], scanline.framebuffer[..8 * SCREEN_WIDTH]);
+    }
+
+    #[test]
+    fn mid_line_palette_change() {
+        // Set up ppu for test, every background pixel is colour 3
+        let mut ppu = ppu_for_test();
+        for byte in ppu.vram[0..16].iter_mut() {
+            *byte = 0xFF;
+        }
+
+        // Run test and compare output, BGP changes after 80 pixels have been drawn
+        ppu.start_fifo_line();
+        while ppu.fifo.lcd_x < 80 {
+            ppu.fifo_step();
+        }
+        ppu.bgp = 0x00;
+        while !ppu.fifo_step() {}
+
+        assert_eq!(ppu.framebuffer[79], 3);
+        assert_eq!(ppu.framebuffer[80], 0);
+    }
+
+    #[test]
+    fn palette_write_lands_mid_instruction() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test, a nop to idle on then ldh (BGP), a with A = 0
+        gameboy.memory.ppu = ppu_for_test();
+        gameboy.memory.ppu.renderer = Renderer::Fifo;
+        for byte in gameboy.memory.ppu.vram[0..16].iter_mut() {
+            *byte = 0xFF;
+        }
+        gameboy.write_instruction(0xC000, 0x00);
+        gameboy.write_instruction(0xC001, 0xE0);
+        gameboy.write_instruction(0xC002, 0x47);
+        gameboy.cpu.register.a = 0x00;
+
+        // Run test and compare output, the write is the third M-cycle of the ldh so it takes effect
+        // 12 pixels after the instruction starts rather than at its start
+        while gameboy.memory.ppu.fifo.lcd_x < 40 {
+            gameboy.cpu.register.pc = 0xC000;
+            gameboy.fetch();
+        }
+        let start = gameboy.memory.ppu.fifo.lcd_x as usize;
+        gameboy.fetch();
+        while gameboy.memory.ppu.ly == 0 {
+            gameboy.cpu.register.pc = 0xC000;
+            gameboy.fetch();
+        }
+
+        assert_eq!(gameboy.memory.ppu.framebuffer[start + 11], 3);
+        assert_eq!(gameboy.memory.ppu.framebuffer[start + 12], 0);
+    }
+
+    #[test]
+    #[ignore = "needs dmg-acid2 in TEST/dmg-acid2"]
+    fn dmg_acid2() {
+        let passed = run_screenshot("TEST/dmg-acid2/dmg-acid2.gb", "TEST/dmg-acid2/reference-dmg.png", Renderer::Fifo);
+        assert_eq!(passed, true);
+    }
+
+    #[test]
+    #[ignore = "needs the mealybug-tearoom tests in TEST/mealybug"]
+    fn mealybug_tearoom() {
+        for name in [
+            "m3_bgp_change", "m3_bgp_change_sprites", "m3_lcdc_bg_en_change", "m3_lcdc_bg_map_change",
+            "m3_lcdc_obj_en_change", "m3_lcdc_obj_size_change", "m3_lcdc_tile_sel_change",
+            "m3_lcdc_win_en_change_multiple", "m3_lcdc_win_map_change", "m3_obp0_change", "m3_scx_high_5_bits",
+            "m3_scx_low_3_bits", "m3_scy_change", "m3_window_timing", "m3_wx_4_change", "m3_wx_5_change",
+            "m3_wx_6_change"
+        ] {
+            let rom = format!("TEST/mealybug/{}.gb", name);
+            let reference = format!("TEST/mealybug/expected/DMG-blob/{}.png", name);
+            assert_eq!(run_screenshot(&rom, &reference, Renderer::Fifo), true, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,461 @@
+mod fifo;
+mod scanline;
+
+use fifo::Fifo;
+use crate::interrupt::Interrupt;
+use crate::mmu::IoDevice;
+
+pub const LCDC: u16 = 0xFF40;
+pub const STAT: u16 = 0xFF41;
+pub const SCY: u16 = 0xFF42;
+pub const SCX: u16 = 0xFF43;
+pub const LY: u16 = 0xFF44;
+pub const LYC: u16 = 0xFF45;
+pub const BGP: u16 = 0xFF47;
+pub const OBP0: u16 = 0xFF48;
+pub const OBP1: u16 = 0xFF49;
+pub const WY: u16 = 0xFF4A;
+pub const WX: u16 = 0xFF4B;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: u16 = 456;
+const LINES_PER_FRAME: u8 = 154;
+const OAM_SCAN_DOTS: u16 = 80;
+const DRAWING_DOTS: u16 = 172;
+// LY switches from 153 to 0 this far into the last line
+const LY_153_DOTS: u16 = 4;
+
+// LCDC bits
+const LCD_ENABLE: u8 = 0x80;
+const WINDOW_TILE_MAP: u8 = 0x40;
+const WINDOW_ENABLE: u8 = 0x20;
+const TILE_DATA: u8 = 0x10;
+const BG_TILE_MAP: u8 = 0x08;
+const OBJ_SIZE: u8 = 0x04;
+const OBJ_ENABLE: u8 = 0x02;
+const BG_ENABLE: u8 = 0x01;
+
+// STAT interrupt source enables
+const STAT_LYC: u8 = 0x40;
+const STAT_OAM: u8 = 0x20;
+const STAT_VBLANK: u8 = 0x10;
+const STAT_HBLANK: u8 = 0x08;
+
+// How mode 3 turns VRAM into pixels
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Renderer {
+    // Draws each line in one go with a fixed length mode 3, fast but blind to mid-line writes
+    Scanline,
+    // Runs the background and sprite fetchers and pixel FIFOs dot by dot like the hardware
+    Fifo
+}
+
+// Value of the STAT mode bits
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3
+}
+
+// Picture processing unit. Walks the LCD timing one dot at a time and draws each line into the
+// framebuffer with the selected renderer.
+pub struct Ppu {
+    pub renderer: Renderer,
+    fifo: Fifo,
+    // 0x8000 - 0x9FFF
+    pub vram: [u8; 0x2000],
+    // 0xFE00 - 0xFE9F
+    pub oam: [u8; 0xA0],
+    pub lcdc: u8,
+    // Only the interrupt enable bits are stored, the mode comes from `mode`
+    stat: u8,
+    // LY=LYC flag, only updated while the LCD is on so it holds its last value while off
+    coincidence: bool,
+    // All enabled STAT sources ORed together, the interrupt fires when this goes high
+    stat_line: bool,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub lyc: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wy: u8,
+    pub wx: u8,
+    pub mode: Mode,
+    // Position within the current line
+    dot: u16,
+    // Set once LY has matched WY this frame, the window can't start before that
+    window_y_triggered: bool,
+    // Window row to draw next, only advances on lines where the window was visible
+    window_line: u8,
+    // Shades 0 (white) to 3 (black) after palette lookup, one byte per pixel
+    pub framebuffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    // Set on entering VBlank, cleared by whoever presents the frame
+    pub frame_ready: bool,
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            renderer: Renderer::Scanline,
+            fifo: Fifo::new(),
+            vram: [0x00; 0x2000],
+            oam: [0x00; 0xA0],
+            lcdc: 0x00,
+            stat: 0x00,
+            coincidence: false,
+            stat_line: false,
+            scy: 0x00,
+            scx: 0x00,
+            ly: 0x00,
+            lyc: 0x00,
+            bgp: 0x00,
+            obp0: 0x00,
+            obp1: 0x00,
+            wy: 0x00,
+            wx: 0x00,
+            mode: Mode::HBlank,
+            dot: 0,
+            window_y_triggered: false,
+            window_line: 0,
+            framebuffer: [0x00; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+        }
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCD_ENABLE != 0
+    }
+
+    // Advances the PPU by a number of M-cycles and returns the IF bits it requested
+    pub fn tick(&mut self, cycles: u8) -> u8 {
+        let mut interrupts = 0;
+        if !self.lcd_enabled() {
+            return interrupts;
+        }
+
+        for _ in 0..cycles as u16 * 4 {
+            interrupts |= self.step_dot();
+        }
+
+        interrupts
+    }
+
+    fn step_dot(&mut self) -> u8 {
+        let mut interrupts = 0;
+        self.dot += 1;
+
+        if self.mode != Mode::VBlank {
+            if self.dot == OAM_SCAN_DOTS {
+                self.mode = Mode::Drawing;
+                if self.renderer == Renderer::Fifo {
+                    self.start_fifo_line();
+                }
+            }
+            else if self.mode == Mode::Drawing {
+                let line_done = match self.renderer {
+                    Renderer::Scanline if self.dot == OAM_SCAN_DOTS + DRAWING_DOTS => {
+                        self.render_scanline();
+                        true
+                    }
+                    Renderer::Scanline => false,
+                    // Mode 3 lasts as long as the FIFO takes to push out 160 pixels
+                    Renderer::Fifo => self.fifo_step()
+                };
+                if line_done {
+                    self.mode = Mode::HBlank;
+                }
+            }
+        }
+
+        if self.dot == DOTS_PER_LINE {
+            self.dot = 0;
+
+            // LY already reads 0 for most of line 153, so a 0 during VBlank means the frame is over
+            if self.mode == Mode::VBlank && self.ly == 0 {
+                self.start_frame();
+                self.mode = Mode::OamScan;
+            }
+            else {
+                self.ly += 1;
+                if self.ly == SCREEN_HEIGHT as u8 {
+                    self.frame_ready = true;
+                    interrupts |= Interrupt::VBlank.bit();
+                    self.mode = Mode::VBlank;
+                }
+                else if self.ly < SCREEN_HEIGHT as u8 {
+                    self.mode = Mode::OamScan;
+                }
+            }
+
+            if self.ly == self.wy {
+                self.window_y_triggered = true;
+            }
+        }
+        else if self.dot == LY_153_DOTS && self.ly == LINES_PER_FRAME - 1 {
+            self.ly = 0;
+        }
+
+        interrupts | self.update_stat()
+    }
+
+    // Refreshes the LY=LYC flag and returns the STAT interrupt on a rising edge of the STAT line.
+    // A source that goes high while another one is already holding the line up is lost.
+    fn update_stat(&mut self) -> u8 {
+        self.coincidence = self.ly == self.lyc;
+
+        let mode_source = match self.mode {
+            Mode::HBlank => self.stat & STAT_HBLANK != 0,
+            // The OAM source also fires when line 144 starts, even though it's VBlank
+            Mode::VBlank => {
+                self.stat & STAT_VBLANK != 0
+                    || (self.stat & STAT_OAM != 0 && self.ly == SCREEN_HEIGHT as u8 && self.dot == 0)
+            }
+            Mode::OamScan => self.stat & STAT_OAM != 0,
+            Mode::Drawing => false
+        };
+        let stat_line = mode_source || (self.coincidence && self.stat & STAT_LYC != 0);
+
+        let rising = stat_line && !self.stat_line;
+        self.stat_line = stat_line;
+        if rising {
+            Interrupt::LcdStat.bit()
+        }
+        else {
+            0
+        }
+    }
+
+    fn start_frame(&mut self) {
+        self.ly = 0;
+        self.window_line = 0;
+        self.window_y_triggered = false;
+    }
+
+    fn write_lcdc(&mut self, data: u8) {
+        let was_enabled = self.lcd_enabled();
+        self.lcdc = data;
+
+        // Switching the LCD off resets it to the top of the screen, it starts again from there.
+        // The STAT line drops while it's off, so the first match after switching back on fires.
+        if was_enabled && !self.lcd_enabled() {
+            self.dot = 0;
+            self.mode = Mode::HBlank;
+            self.stat_line = false;
+            self.start_frame();
+        }
+        else if !was_enabled && self.lcd_enabled() {
+            self.dot = 0;
+            self.mode = Mode::OamScan;
+            self.window_y_triggered = self.ly == self.wy;
+        }
+    }
+}
+
+impl IoDevice for Ppu {
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            LCDC => self.lcdc,
+            STAT => {
+                let coincidence = if self.coincidence { 0x04 } else { 0x00 };
+                0x80 | self.stat | coincidence | self.mode as u8
+            }
+            SCY => self.scy,
+            SCX => self.scx,
+            LY => self.ly,
+            LYC => self.lyc,
+            BGP => self.bgp,
+            OBP0 => self.obp0,
+            OBP1 => self.obp1,
+            WY => self.wy,
+            WX => self.wx,
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        match address {
+            LCDC => self.write_lcdc(data),
+            STAT => self.stat = data & 0x78,
+            SCY => self.scy = data,
+            SCX => self.scx = data,
+            // LY is read only
+            LY => {},
+            LYC => self.lyc = data,
+            BGP => self.bgp = data,
+            OBP0 => self.obp0 = data,
+            OBP1 => self.obp1 = data,
+            WY => self.wy = data,
+            WX => self.wx = data,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_timing() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC, 0x80);
+
+        // Run test and compare output
+        assert_eq!(ppu.mode, Mode::OamScan);
+        ppu.tick(20);
+        assert_eq!(ppu.mode, Mode::Drawing);
+        ppu.tick(43);
+        assert_eq!(ppu.mode, Mode::HBlank);
+        ppu.tick(50);
+        assert_eq!(ppu.mode, Mode::HBlank);
+        assert_eq!(ppu.ly, 0);
+        ppu.tick(1);
+        assert_eq!(ppu.mode, Mode::OamScan);
+        assert_eq!(ppu.ly, 1);
+    }
+
+    #[test]
+    fn vblank_interrupt() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC, 0x80);
+
+        // Run test and compare output, 144 lines of 114 M-cycles
+        let mut interrupts = 0;
+        for _ in 0..144 * 114 - 1 {
+            interrupts |= ppu.tick(1);
+        }
+        assert_eq!(interrupts & Interrupt::VBlank.bit(), 0);
+        assert_eq!(ppu.tick(1) & Interrupt::VBlank.bit(), Interrupt::VBlank.bit());
+        assert_eq!(ppu.ly, 144);
+        assert_eq!(ppu.mode, Mode::VBlank);
+        assert_eq!(ppu.frame_ready, true);
+
+        // Ten lines of VBlank before the next frame starts
+        for _ in 0..10 * 114 {
+            ppu.tick(1);
+        }
+        assert_eq!(ppu.ly, 0);
+        assert_eq!(ppu.mode, Mode::OamScan);
+    }
+
+    #[test]
+    fn stat_interrupts() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC, 0x80);
+        ppu.write(STAT, STAT_HBLANK);
+
+        // Run test and compare output
+        assert_eq!(ppu.tick(20), 0);
+        assert_eq!(ppu.tick(43), Interrupt::LcdStat.bit());
+
+        ppu.write(STAT, STAT_LYC);
+        ppu.write(LYC, 1);
+        assert_eq!(ppu.tick(51), Interrupt::LcdStat.bit());
+        assert_eq!(ppu.read(STAT), 0x80 | STAT_LYC | 0x04 | Mode::OamScan as u8);
+    }
+
+    #[test]
+    fn stat_blocking() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC, 0x80);
+        ppu.tick(63);
+        ppu.write(STAT, STAT_HBLANK | STAT_OAM);
+
+        // Run test and compare output, the line is already high from OAM scan when HBlank ends
+        assert_eq!(ppu.tick(1), Interrupt::LcdStat.bit());
+        assert_eq!(ppu.tick(50), 0);
+        assert_eq!(ppu.mode, Mode::OamScan);
+
+        // It drops for mode 3, so the next HBlank raises it again
+        assert_eq!(ppu.tick(20), 0);
+        assert_eq!(ppu.tick(43), Interrupt::LcdStat.bit());
+    }
+
+    #[test]
+    fn line_153_reads_zero() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC, 0x80);
+        for _ in 0..153 {
+            ppu.tick(114);
+        }
+        ppu.write(LYC, 0);
+        ppu.write(STAT, STAT_LYC);
+
+        // Run test and compare output, LY is 153 for a single M-cycle
+        assert_eq!(ppu.ly, 153);
+        assert_eq!(ppu.tick(1), Interrupt::LcdStat.bit());
+        assert_eq!(ppu.ly, 0);
+        assert_eq!(ppu.mode, Mode::VBlank);
+        assert_eq!(ppu.read(STAT) & 0x04, 0x04);
+
+        // The match carries on into line 0 of the next frame without another interrupt
+        assert_eq!(ppu.tick(113), 0);
+        assert_eq!(ppu.ly, 0);
+        assert_eq!(ppu.mode, Mode::OamScan);
+        assert_eq!(ppu.tick(114), 0);
+        assert_eq!(ppu.ly, 1);
+    }
+
+    #[test]
+    fn fifo_mode_3_length() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.renderer = Renderer::Fifo;
+        ppu.write(LCDC, 0x80);
+        ppu.write(SCX, 0x04);
+
+        // Run test and compare output, 80 dots of OAM scan then 172 + 4 of drawing
+        ppu.tick(63);
+        assert_eq!(ppu.mode, Mode::Drawing);
+        ppu.tick(1);
+        assert_eq!(ppu.mode, Mode::HBlank);
+    }
+
+    #[test]
+    fn lcd_off() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC, 0x80);
+        for _ in 0..3 {
+            ppu.tick(114);
+        }
+
+        // Run test and compare output
+        ppu.write(LCDC, 0x00);
+        assert_eq!(ppu.ly, 0);
+        assert_eq!(ppu.read(STAT) & 0x03, 0);
+        assert_eq!(ppu.tick(114), 0);
+        assert_eq!(ppu.ly, 0);
+    }
+
+    #[test]
+    fn lcd_off_holds_coincidence() {
+        // Set up ppu for test
+        let mut ppu = Ppu::new();
+        ppu.write(LYC, 1);
+        ppu.write(LCDC, 0x80);
+        ppu.write(STAT, STAT_LYC);
+        ppu.tick(114);
+
+        // Run test and compare output, the flag keeps its value while LY sits at 0
+        ppu.write(LCDC, 0x00);
+        assert_eq!(ppu.read(STAT) & 0x04, 0x04);
+        ppu.write(LYC, 0);
+        assert_eq!(ppu.read(STAT) & 0x04, 0x04);
+
+        // Switching back on compares again, and the dropped line lets the match interrupt
+        ppu.write(LCDC, 0x80);
+        assert_eq!(ppu.tick(1), Interrupt::LcdStat.bit());
+    }
+}
@@ -0,0 +1,323 @@
+use super::*;
+
+// Sprites the hardware can show on a single line
+const SPRITES_PER_LINE: usize = 10;
+
+// One entry in OAM
+#[derive(Copy, Clone)]
+pub struct Sprite {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+    // Position in OAM, breaks ties between sprites at the same X
+    pub index: usize,
+}
+
+// Sprite attribute bits
+pub const OBJ_BG_PRIORITY: u8 = 0x80;
+pub const OBJ_Y_FLIP: u8 = 0x40;
+pub const OBJ_X_FLIP: u8 = 0x20;
+pub const OBJ_PALETTE: u8 = 0x10;
+
+impl Ppu {
+    // Draws the whole of line LY in one go using the register values at the end of mode 3
+    pub fn render_scanline(&mut self) {
+        let line_start = self.ly as usize * SCREEN_WIDTH;
+        // Colour numbers before the palette, sprites need them for BG-over-OBJ
+        let mut bg_colours = [0u8; SCREEN_WIDTH];
+        let mut window_drawn = false;
+
+        // On DMG clearing LCDC bit 0 blanks both the background and the window
+        if self.lcdc & BG_ENABLE != 0 {
+            for (x, bg_colour) in bg_colours.iter_mut().enumerate() {
+                *bg_colour = if self.window_visible_at(x) {
+                    window_drawn = true;
+                    let window_x = (x + 7 - self.wx as usize) as u8;
+                    self.tile_map_colour(self.lcdc & WINDOW_TILE_MAP != 0, window_x, self.window_line)
+                }
+                else {
+                    let bg_x = self.scx.wrapping_add(x as u8);
+                    let bg_y = self.scy.wrapping_add(self.ly);
+                    self.tile_map_colour(self.lcdc & BG_TILE_MAP != 0, bg_x, bg_y)
+                };
+            }
+        }
+
+        for (x, bg_colour) in bg_colours.iter().enumerate() {
+            self.framebuffer[line_start + x] = palette_shade(self.bgp, *bg_colour);
+        }
+
+        if window_drawn {
+            self.window_line += 1;
+        }
+
+        if self.lcdc & OBJ_ENABLE != 0 {
+            self.render_sprites(&bg_colours);
+        }
+    }
+
+    pub fn window_visible_at(&self, x: usize) -> bool {
+        self.lcdc & WINDOW_ENABLE != 0 && self.window_y_triggered && x + 7 >= self.wx as usize
+    }
+
+    pub fn sprite_height(&self) -> u8 {
+        if self.lcdc & OBJ_SIZE != 0 {
+            16
+        }
+        else {
+            8
+        }
+    }
+
+    // OAM scan, the first ten sprites in OAM order that overlap this line
+    pub fn sprites_on_line(&self) -> Vec<Sprite> {
+        let height = self.sprite_height() as u16;
+        let line = self.ly as u16 + 16;
+
+        (0..40)
+            .map(|index| Sprite {
+                y: self.oam[index * 4],
+                x: self.oam[index * 4 + 1],
+                tile: self.oam[index * 4 + 2],
+                attributes: self.oam[index * 4 + 3],
+                index,
+            })
+            .filter(|sprite| line >= sprite.y as u16 && line < sprite.y as u16 + height)
+            .take(SPRITES_PER_LINE)
+            .collect()
+    }
+
+    fn render_sprites(&mut self, bg_colours: &[u8; SCREEN_WIDTH]) {
+        let mut sprites = self.sprites_on_line();
+        // On DMG the sprite further left wins, then the one earlier in OAM
+        sprites.sort_by_key(|sprite| (sprite.x, sprite.index));
+
+        let line_start = self.ly as usize * SCREEN_WIDTH;
+        for (x, bg_colour) in bg_colours.iter().enumerate() {
+            // Sprite X is offset by 8 so sprites can scroll in from the left edge
+            let screen_x = x as i16 + 8;
+            let pixel = sprites.iter().find_map(|sprite| {
+                let column = screen_x - sprite.x as i16;
+                if !(0..8).contains(&column) {
+                    return None;
+                }
+                let colour = self.sprite_colour(sprite, column as u8);
+                (colour != 0).then_some((sprite, colour))
+            });
+
+            if let Some((sprite, colour)) = pixel {
+                if sprite.attributes & OBJ_BG_PRIORITY != 0 && *bg_colour != 0 {
+                    continue;
+                }
+                let palette = if sprite.attributes & OBJ_PALETTE != 0 { self.obp1 } else { self.obp0 };
+                self.framebuffer[line_start + x] = palette_shade(palette, colour);
+            }
+        }
+    }
+
+    // Colour number of a sprite pixel on the current line, column counts from the sprite's left edge
+    pub fn sprite_colour(&self, sprite: &Sprite, column: u8) -> u8 {
+        let height = self.sprite_height();
+        let mut row = self.ly.wrapping_add(16).wrapping_sub(sprite.y);
+        if sprite.attributes & OBJ_Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+
+        // 8x16 sprites ignore bit 0 of the tile number and use the next tile for the bottom half
+        let tile = if height == 16 { sprite.tile & 0xFE } else { sprite.tile };
+        let address = tile as usize * 16 + row as usize * 2;
+
+        let bit = if sprite.attributes & OBJ_X_FLIP != 0 { column } else { 7 - column };
+        tile_pixel(self.vram[address], self.vram[address + 1], bit)
+    }
+
+    // Colour number at a position in one of the two 32x32 tile maps
+    pub fn tile_map_colour(&self, high_map: bool, x: u8, y: u8) -> u8 {
+        let map = if high_map { 0x1C00 } else { 0x1800 };
+        let tile = self.vram[map + (y as usize / 8) * 32 + x as usize / 8];
+        let address = self.tile_address(tile) + (y as usize % 8) * 2;
+        tile_pixel(self.vram[address], self.vram[address + 1], 7 - x % 8)
+    }
+
+    // VRAM offset of a background or window tile, LCDC bit 4 picks unsigned indexing from 0x8000
+    // or signed indexing around 0x9000
+    pub fn tile_address(&self, tile: u8) -> usize {
+        if self.lcdc & TILE_DATA != 0 {
+            tile as usize * 16
+        }
+        else {
+            (0x1000 + tile as i8 as isize * 16) as usize
+        }
+    }
+}
+
+// Combines the two bitplanes of a tile row into the colour number of one pixel
+pub fn tile_pixel(low: u8, high: u8, bit: u8) -> u8 {
+    (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+}
+
+pub fn palette_shade(palette: u8, colour: u8) -> u8 {
+    (palette >> (colour * 2)) & 0x03
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fills a tile with a single colour number
+    fn solid_tile(ppu: &mut Ppu, address: usize, colour: u8) {
+        for row in 0..8 {
+            ppu.vram[address + row * 2] = if colour & 1 != 0 { 0xFF } else { 0x00 };
+            ppu.vram[address + row * 2 + 1] = if colour & 2 != 0 { 0xFF } else { 0x00 };
+        }
+    }
+
+    fn ppu_for_test() -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.lcdc = LCD_ENABLE | TILE_DATA | BG_ENABLE;
+        ppu.bgp = 0xE4;
+        ppu.obp0 = 0xE4;
+        ppu.obp1 = 0x1B;
+        ppu
+    }
+
+    #[test]
+    fn background_scroll() {
+        // Set up ppu for test, tile 1 is colour 3 and sits at map position (1, 0)
+        let mut ppu = ppu_for_test();
+        solid_tile(&mut ppu, 16, 3);
+        ppu.vram[0x1801] = 1;
+
+        // Run test and compare output
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[7], 0);
+        assert_eq!(ppu.framebuffer[8], 3);
+
+        ppu.scx = 4;
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[3], 0);
+        assert_eq!(ppu.framebuffer[4], 3);
+    }
+
+    #[test]
+    fn signed_tile_data() {
+        // Set up ppu for test, tile 0xFF sits just below 0x9000
+        let mut ppu = ppu_for_test();
+        ppu.lcdc &= !TILE_DATA;
+        solid_tile(&mut ppu, 0x0FF0, 2);
+        ppu.vram[0x1800] = 0xFF;
+
+        // Run test and compare output
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[0], 2);
+    }
+
+    #[test]
+    fn window_line_counter() {
+        // Set up ppu for test, window map at 0x9C00 uses tile 1 on row 0 and tile 2 on row 1
+        let mut ppu = ppu_for_test();
+        ppu.lcdc |= WINDOW_ENABLE | WINDOW_TILE_MAP;
+        solid_tile(&mut ppu, 16, 1);
+        solid_tile(&mut ppu, 32, 2);
+        ppu.vram[0x1C00] = 1;
+        ppu.vram[0x1C20] = 2;
+        ppu.wx = 7 + 80;
+        ppu.window_y_triggered = true;
+
+        // Run test and compare output
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[79], 0);
+        assert_eq!(ppu.framebuffer[80], 1);
+
+        // Disabling the window for some lines pauses its line counter
+        ppu.lcdc &= !WINDOW_ENABLE;
+        for line in 1..20 {
+            ppu.ly = line;
+            ppu.render_scanline();
+        }
+        ppu.lcdc |= WINDOW_ENABLE;
+        for line in 20..27 {
+            ppu.ly = line;
+            ppu.render_scanline();
+        }
+        assert_eq!(ppu.framebuffer[26 * SCREEN_WIDTH + 80], 1);
+        ppu.ly = 27;
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[27 * SCREEN_WIDTH + 80], 2);
+    }
+
+    #[test]
+    fn sprite_priority_and_limit() {
+        // Set up ppu for test, tile 1 is colour 1 and tile 2 is colour 2
+        let mut ppu = ppu_for_test();
+        ppu.lcdc |= OBJ_ENABLE;
+        solid_tile(&mut ppu, 16, 1);
+        solid_tile(&mut ppu, 32, 2);
+
+        // Two overlapping sprites, the one further left wins even though it's later in OAM
+        ppu.oam[0..4].copy_from_slice(&[16, 12, 1, 0]);
+        ppu.oam[4..8].copy_from_slice(&[16, 10, 2, 0]);
+
+        // Run test and compare output
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[5], 2);
+        assert_eq!(ppu.framebuffer[10], 1);
+
+        // Only the first ten sprites on a line are drawn
+        for index in 0..12 {
+            ppu.oam[index * 4..index * 4 + 4].copy_from_slice(&[16, 8 + index as u8 * 8, 1, 0]);
+        }
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[9 * 8], 1);
+        assert_eq!(ppu.framebuffer[10 * 8], 0);
+    }
+
+    #[test]
+    fn sprite_flip_and_tall_sprites() {
+        // Set up ppu for test, tile 2 is colour 1 and tile 3 is colour 2
+        let mut ppu = ppu_for_test();
+        ppu.lcdc |= OBJ_ENABLE | OBJ_SIZE;
+        solid_tile(&mut ppu, 32, 1);
+        solid_tile(&mut ppu, 48, 2);
+        ppu.oam[0..4].copy_from_slice(&[16, 8, 3, 0]);
+
+        // Run test and compare output, bit 0 of the tile is ignored
+        ppu.ly = 0;
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[0], 1);
+        ppu.ly = 15;
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[15 * SCREEN_WIDTH], 2);
+
+        ppu.oam[3] = OBJ_Y_FLIP | OBJ_PALETTE;
+        ppu.ly = 0;
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[0], palette_shade(0x1B, 2));
+
+        // X flip mirrors a single pixel column
+        ppu.lcdc &= !OBJ_SIZE;
+        ppu.vram[16] = 0x80;
+        ppu.vram[17] = 0x00;
+        ppu.oam[0..4].copy_from_slice(&[16, 8, 1, OBJ_X_FLIP]);
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[0], 0);
+        assert_eq!(ppu.framebuffer[7], 1);
+    }
+
+    #[test]
+    fn bg_over_obj() {
+        // Set up ppu for test, background colour 1 on the left tile and 0 on the right
+        let mut ppu = ppu_for_test();
+        ppu.lcdc |= OBJ_ENABLE;
+        solid_tile(&mut ppu, 16, 1);
+        solid_tile(&mut ppu, 32, 3);
+        ppu.vram[0x1800] = 1;
+        ppu.oam[0..4].copy_from_slice(&[16, 12, 2, OBJ_BG_PRIORITY]);
+
+        // Run test and compare output, the sprite only shows over background colour 0
+        ppu.render_scanline();
+        assert_eq!(ppu.framebuffer[4], 1);
+        assert_eq!(ppu.framebuffer[8], 3);
+    }
+}
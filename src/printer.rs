@@ -0,0 +1,351 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::serial::SerialEndpoint;
+
+// Commands
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+// Status bits
+const CHECKSUM_ERROR: u8 = 0x01;
+const PRINTING: u8 = 0x02;
+const IMAGE_FULL: u8 = 0x04;
+const UNPROCESSED: u8 = 0x08;
+
+// Sent back in place of the first byte after the checksum to say a printer is plugged in
+const ALIVE: u8 = 0x81;
+
+// The printer holds up to 8 KiB of tile data, a 160x144 picture fits with room to spare
+const IMAGE_CAPACITY: usize = 0x2000;
+const TILES_PER_ROW: usize = 20;
+const WIDTH: usize = TILES_PER_ROW * 8;
+const TILE_ROW_BYTES: usize = TILES_PER_ROW * 16;
+// Pixel rows fed per unit of margin
+const MARGIN_ROWS: usize = 8;
+// Status requests answered as busy after a print, games wait for the printing bit to clear
+const BUSY_POLLS: u8 = 3;
+// Paper shades from white to black
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+// Position within a packet: the magic bytes 88 33, command, compression, length, data,
+// checksum, then two more bytes the printer answers with ALIVE and its status
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PacketState {
+    Magic,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+// Game Boy Printer. It answers on the game's clock, and every print it's told to make is saved
+// as a PNG in the output directory.
+pub struct Printer {
+    output_dir: PathBuf,
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    // Sum of every byte from the command to the end of the data
+    checksum: u16,
+    received_checksum: u16,
+    // Tile data waiting to be printed
+    image: Vec<u8>,
+    checksum_error: bool,
+    busy_polls: u8,
+    prints: u32,
+}
+
+impl Printer {
+    pub fn new(output_dir: &Path) -> Self {
+        Self {
+            output_dir: output_dir.to_path_buf(),
+            state: PacketState::Magic,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            image: Vec::new(),
+            checksum_error: false,
+            busy_polls: 0,
+            prints: 0,
+        }
+    }
+
+    // Path the next print is saved to
+    pub fn print_path(&self) -> PathBuf {
+        self.output_dir.join(format!("print_{:03}.png", self.prints + 1))
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = 0;
+        if self.checksum_error {
+            status |= CHECKSUM_ERROR;
+        }
+        if self.busy_polls > 0 {
+            status |= PRINTING | IMAGE_FULL;
+        }
+        if !self.image.is_empty() {
+            status |= UNPROCESSED;
+        }
+        status
+    }
+
+    fn run_command(&mut self) {
+        match self.command {
+            INIT => {
+                self.image.clear();
+                self.busy_polls = 0;
+            }
+            DATA => {
+                let data = if self.compressed { decompress(&self.data) } else { self.data.clone() };
+                let space = IMAGE_CAPACITY - self.image.len();
+                self.image.extend_from_slice(&data[..data.len().min(space)]);
+            }
+            PRINT if self.data.len() == 4 => {
+                let (sheets, margins, palette) = (self.data[0], self.data[1], self.data[2]);
+                // No sheets only feeds the paper
+                if sheets > 0 {
+                    if let Err(error) = self.print(margins, palette) {
+                        eprintln!("{}: {}", self.print_path().display(), error);
+                    }
+                }
+                self.image.clear();
+                self.busy_polls = BUSY_POLLS;
+            }
+            STATUS => self.busy_polls = self.busy_polls.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, margins: u8, palette: u8) -> io::Result<()> {
+        let pixels = render(&self.image, margins, palette);
+        fs::create_dir_all(&self.output_dir)?;
+        save_png(&self.print_path(), &pixels)?;
+        self.prints += 1;
+        Ok(())
+    }
+}
+
+impl SerialEndpoint for Printer {
+    fn transfer(&mut self, data: u8) -> u8 {
+        let mut reply = 0x00;
+        self.state = match self.state {
+            PacketState::Magic if data == 0x88 => PacketState::Magic2,
+            PacketState::Magic => PacketState::Magic,
+            PacketState::Magic2 if data == 0x33 => PacketState::Command,
+            PacketState::Magic2 if data == 0x88 => PacketState::Magic2,
+            PacketState::Magic2 => PacketState::Magic,
+            PacketState::Command => {
+                self.command = data;
+                self.checksum = data as u16;
+                PacketState::Compression
+            }
+            PacketState::Compression => {
+                self.compressed = data & 0x01 != 0;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                PacketState::LengthLow
+            }
+            PacketState::LengthLow => {
+                self.length = data as u16;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                PacketState::LengthHigh
+            }
+            PacketState::LengthHigh => {
+                self.length |= (data as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                self.data.clear();
+                if self.length == 0 { PacketState::ChecksumLow } else { PacketState::Data }
+            }
+            PacketState::Data => {
+                self.data.push(data);
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                if self.data.len() == self.length as usize { PacketState::ChecksumLow } else { PacketState::Data }
+            }
+            PacketState::ChecksumLow => {
+                self.received_checksum = data as u16;
+                PacketState::ChecksumHigh
+            }
+            PacketState::ChecksumHigh => {
+                self.received_checksum |= (data as u16) << 8;
+                PacketState::Alive
+            }
+            PacketState::Alive => {
+                reply = ALIVE;
+                PacketState::Status
+            }
+            PacketState::Status => {
+                // The status sent is from before the command runs, a print reports its data as
+                // still unprocessed
+                self.checksum_error = self.checksum != self.received_checksum;
+                reply = self.status();
+                if !self.checksum_error {
+                    self.run_command();
+                }
+                PacketState::Magic
+            }
+        };
+        reply
+    }
+}
+
+// Expands the printer's RLE: a control byte with bit 7 set repeats the next byte
+// (control & 0x7F) + 2 times, otherwise (control + 1) bytes follow as they are
+pub fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut bytes = data.iter();
+    while let Some(&control) = bytes.next() {
+        if control & 0x80 != 0 {
+            if let Some(&byte) = bytes.next() {
+                output.extend(std::iter::repeat_n(byte, (control & 0x7F) as usize + 2));
+            }
+        }
+        else {
+            output.extend(bytes.by_ref().take(control as usize + 1));
+        }
+    }
+    output
+}
+
+// Turns tile data into rows of grey pixels 160 wide. The high nibble of the margins is the
+// feed before the picture and the low nibble the feed after, palette 0 prints as E4.
+fn render(image: &[u8], margins: u8, palette: u8) -> Vec<u8> {
+    let palette = if palette == 0 { 0xE4 } else { palette };
+    let tile_rows = image.len() / TILE_ROW_BYTES;
+    let top = (margins >> 4) as usize * MARGIN_ROWS;
+    let bottom = (margins & 0x0F) as usize * MARGIN_ROWS;
+
+    let mut pixels = vec![SHADES[0]; WIDTH * (top + tile_rows * 8 + bottom)];
+    for y in 0..tile_rows * 8 {
+        for x in 0..WIDTH {
+            let tile = (y / 8) * TILE_ROW_BYTES + (x / 8) * 16;
+            let low = image[tile + (y % 8) * 2];
+            let high = image[tile + (y % 8) * 2 + 1];
+            let bit = 7 - (x % 8);
+            let colour = ((high >> bit) & 0x01) << 1 | ((low >> bit) & 0x01);
+            let shade = (palette >> (colour * 2)) & 0x03;
+            pixels[(top + y) * WIDTH + x] = SHADES[shade as usize];
+        }
+    }
+    pixels
+}
+
+fn save_png(path: &Path, pixels: &[u8]) -> io::Result<()> {
+    let height = pixels.len() / WIDTH;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), WIDTH as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_roms::temp_dir;
+
+    // Sends a whole packet the way a game does and returns the last two bytes the printer answered with
+    fn send_packet(printer: &mut Printer, command: u8, compressed: bool, data: &[u8]) -> (u8, u8) {
+        let mut packet = vec![0x88, 0x33, command, compressed as u8, data.len() as u8, (data.len() >> 8) as u8];
+        packet.extend_from_slice(data);
+        let checksum = packet[2..].iter().fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+        packet.extend_from_slice(&[checksum as u8, (checksum >> 8) as u8, 0x00, 0x00]);
+
+        let replies: Vec<u8> = packet.iter().map(|byte| printer.transfer(*byte)).collect();
+        assert_eq!(replies[..replies.len() - 2].iter().all(|reply| *reply == 0x00), true);
+        (replies[replies.len() - 2], replies[replies.len() - 1])
+    }
+
+    fn decode_png(path: &Path) -> (u32, u32, Vec<u8>) {
+        let decoder = png::Decoder::new(File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        (info.width, info.height, pixels)
+    }
+
+    #[test]
+    fn rle_decompression() {
+        assert_eq!(decompress(&[0x81, 0xAB, 0x01, 0x12, 0x34, 0x80, 0xFF]), [0xAB, 0xAB, 0xAB, 0x12, 0x34, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn prints_png() {
+        // Set up printer for test, one tile row where every tile has a stripe of each colour
+        let dir = temp_dir("printer_print");
+        let mut printer = Printer::new(&dir);
+        let tile = [0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF].repeat(2);
+        let tile_row = tile.repeat(TILES_PER_ROW);
+
+        // Run test and compare output
+        assert_eq!(send_packet(&mut printer, INIT, false, &[]), (ALIVE, 0x00));
+        assert_eq!(send_packet(&mut printer, DATA, false, &tile_row), (ALIVE, 0x00));
+        // The same row again compressed, the 16 byte tile pattern repeated as literals
+        let mut compressed = Vec::new();
+        for _ in 0..TILES_PER_ROW {
+            compressed.push(0x0F);
+            compressed.extend_from_slice(&tile);
+        }
+        assert_eq!(send_packet(&mut printer, DATA, true, &compressed), (ALIVE, UNPROCESSED));
+        assert_eq!(send_packet(&mut printer, DATA, false, &[]), (ALIVE, UNPROCESSED));
+        assert_eq!(send_packet(&mut printer, STATUS, false, &[]), (ALIVE, UNPROCESSED));
+
+        // One sheet, one unit of margin above and two below, colours printed inverted
+        assert_eq!(send_packet(&mut printer, PRINT, false, &[0x01, 0x12, 0x1B, 0x40]), (ALIVE, UNPROCESSED));
+        assert_eq!(send_packet(&mut printer, STATUS, false, &[]), (ALIVE, PRINTING | IMAGE_FULL));
+
+        let (width, height, pixels) = decode_png(&dir.join("print_001.png"));
+        assert_eq!((width, height), (160, 8 + 16 + 16));
+        assert_eq!(pixels[..WIDTH * 8].iter().all(|pixel| *pixel == 0xFF), true);
+        let column: Vec<u8> = (8..12).map(|y| pixels[y * WIDTH + 3]).collect();
+        assert_eq!(column, [0x00, 0x55, 0xAA, 0xFF]);
+        assert_eq!(pixels[20 * WIDTH + 3], 0x00);
+        assert_eq!(pixels[WIDTH * 24..].iter().all(|pixel| *pixel == 0xFF), true);
+        assert_eq!(printer.print_path(), dir.join("print_002.png"));
+    }
+
+    #[test]
+    fn busy_after_print() {
+        // Set up printer for test
+        let dir = temp_dir("printer_busy");
+        let mut printer = Printer::new(&dir);
+        send_packet(&mut printer, DATA, false, &[0x00; TILE_ROW_BYTES]);
+        send_packet(&mut printer, PRINT, false, &[0x01, 0x00, 0xE4, 0x40]);
+
+        // Run test and compare output
+        for _ in 0..BUSY_POLLS {
+            assert_eq!(send_packet(&mut printer, STATUS, false, &[]), (ALIVE, PRINTING | IMAGE_FULL));
+        }
+        assert_eq!(send_packet(&mut printer, STATUS, false, &[]), (ALIVE, 0x00));
+        assert_eq!(dir.join("print_001.png").exists(), true);
+    }
+
+    #[test]
+    fn checksum_error() {
+        // Set up printer for test
+        let dir = temp_dir("printer_checksum");
+        let mut printer = Printer::new(&dir);
+
+        // Run test and compare output, a data packet with a bad checksum is dropped
+        let replies: Vec<u8> = [0x88, 0x33, DATA, 0x00, 0x01, 0x00, 0xAA, 0x00, 0x00, 0x00, 0x00]
+            .iter()
+            .map(|byte| printer.transfer(*byte))
+            .collect();
+        assert_eq!(replies[9..], [ALIVE, CHECKSUM_ERROR]);
+        assert_eq!(send_packet(&mut printer, STATUS, false, &[]), (ALIVE, 0x00));
+    }
+}
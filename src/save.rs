@@ -0,0 +1,152 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cartridge::Cartridge;
+
+// Roughly one second of emulated time between periodic flushes
+const FLUSH_INTERVAL: u64 = 1 << 20;
+
+// Keeps a battery backed cartridge's RAM in a .sav file next to the ROM
+pub struct BatterySave {
+    path: PathBuf,
+    // M-cycles since the file was last written
+    cycles_since_flush: u64,
+}
+
+impl BatterySave {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            cycles_since_flush: 0,
+        }
+    }
+
+    // game.gb saves to game.sav
+    pub fn for_rom(rom_path: &Path) -> Self {
+        BatterySave::new(rom_path.with_extension("sav"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Restores RAM from the save file, a missing file just means a fresh game
+    pub fn load(&self, cartridge: &mut Cartridge) -> io::Result<()> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                cartridge.load_save_data(&data);
+                Ok(())
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error)
+        }
+    }
+
+    // Called after each step with the M-cycles it took. Writes the file when the game disables
+    // RAM after writing to it, or once a second while there are unsaved changes.
+    pub fn update(&mut self, cartridge: &mut Cartridge, cycles: u64) -> io::Result<()> {
+        self.cycles_since_flush += cycles;
+
+        if cartridge.flush_requested || (cartridge.ram_dirty && self.cycles_since_flush >= FLUSH_INTERVAL) {
+            self.flush(cartridge)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        write_atomic(&self.path, &cartridge.save_data())?;
+        cartridge.ram_dirty = false;
+        cartridge.flush_requested = false;
+        self.cycles_since_flush = 0;
+        Ok(())
+    }
+}
+
+// Writes to a temporary file and renames it over the target, so a crash part way through leaves
+// the previous save intact
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::tests::build_rom;
+    use crate::test_roms::temp_dir;
+
+    fn battery_cartridge() -> Cartridge {
+        // MBC1+RAM+BATTERY with 8 KiB of RAM
+        Cartridge::from_bytes(build_rom(0x10000, 0x03, 0x01, 0x02)).unwrap()
+    }
+
+    #[test]
+    fn missing_file_is_fresh_game() {
+        // Set up save for test
+        let dir = temp_dir("missing");
+        let save = BatterySave::for_rom(&dir.join("game.gb"));
+        let mut cartridge = battery_cartridge();
+
+        // Run test and compare output
+        assert_eq!(save.path(), dir.join("game.sav"));
+        assert_eq!(save.load(&mut cartridge).is_ok(), true);
+        assert_eq!(cartridge.ram[0], 0x00);
+    }
+
+    #[test]
+    fn flush_on_ram_disable() {
+        // Set up save for test
+        let dir = temp_dir("disable");
+        let mut save = BatterySave::for_rom(&dir.join("game.gb"));
+        let mut cartridge = battery_cartridge();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+
+        // Run test and compare output
+        save.update(&mut cartridge, 4).unwrap();
+        assert_eq!(save.path().exists(), false);
+
+        cartridge.write_rom(0x0000, 0x00);
+        save.update(&mut cartridge, 4).unwrap();
+        assert_eq!(fs::read(save.path()).unwrap()[0], 0x42);
+        assert_eq!(cartridge.flush_requested, false);
+        assert_eq!(dir.join("game.sav.tmp").exists(), false);
+
+        let mut restored = battery_cartridge();
+        save.load(&mut restored).unwrap();
+        assert_eq!(restored.ram[0], 0x42);
+    }
+
+    #[test]
+    fn periodic_flush() {
+        // Set up save for test
+        let dir = temp_dir("periodic");
+        let mut save = BatterySave::for_rom(&dir.join("game.gb"));
+        let mut cartridge = battery_cartridge();
+        cartridge.write_rom(0x0000, 0x0A);
+
+        // Run test and compare output, nothing is written while RAM is unchanged
+        save.update(&mut cartridge, FLUSH_INTERVAL).unwrap();
+        assert_eq!(save.path().exists(), false);
+
+        cartridge.write_ram(0xA000, 0x42);
+        save.update(&mut cartridge, FLUSH_INTERVAL / 2).unwrap();
+        assert_eq!(save.path().exists(), true);
+        assert_eq!(cartridge.ram_dirty, false);
+
+        cartridge.write_ram(0xA000, 0x43);
+        save.update(&mut cartridge, FLUSH_INTERVAL / 2).unwrap();
+        assert_eq!(fs::read(save.path()).unwrap()[0], 0x42);
+        save.update(&mut cartridge, FLUSH_INTERVAL / 2).unwrap();
+        assert_eq!(fs::read(save.path()).unwrap()[0], 0x43);
+    }
+}
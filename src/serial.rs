@@ -0,0 +1,320 @@
+use std::io::{self, Write};
+
+use crate::mmu::IoDevice;
+
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+// SC bits
+const TRANSFER_START: u8 = 0x80;
+const INTERNAL_CLOCK: u8 = 0x01;
+
+// The internal clock runs at 8192Hz, one bit every 128 M-cycles
+const CYCLES_PER_BIT: u16 = 128;
+// How often the endpoint is asked whether the partner has clocked a transfer
+const POLL_INTERVAL: u16 = CYCLES_PER_BIT;
+
+// Whatever is plugged into the other end of the link cable
+pub trait SerialEndpoint {
+    // Called when this Game Boy starts a transfer on its own clock with the byte in SB,
+    // returns the byte the partner shifts back
+    fn transfer(&mut self, data: u8) -> u8;
+
+    // Polled regularly so the partner can clock a transfer in. `data` is the byte in SB while a
+    // transfer is waiting on the partner's clock and None otherwise, in which case the partner
+    // reads 0xFF. Returns the partner's byte once it has clocked a transfer.
+    fn external_transfer(&mut self, _data: Option<u8>) -> Option<u8> {
+        None
+    }
+
+    // Called on every poll with the M-cycles since power on, so an endpoint can keep the
+    // partner's clock in step with ours
+    fn sync(&mut self, _cycles: u64) {}
+}
+
+// Nothing plugged in. The input line floats high and no clock ever arrives.
+pub struct Disconnected;
+
+impl SerialEndpoint for Disconnected {
+    fn transfer(&mut self, _data: u8) -> u8 {
+        0xFF
+    }
+}
+
+// Prints every byte sent as text, Blargg's test ROMs report their results this way
+pub struct StdoutEndpoint;
+
+impl SerialEndpoint for StdoutEndpoint {
+    fn transfer(&mut self, data: u8) -> u8 {
+        print!("{}", data as char);
+        let _ = io::stdout().flush();
+        0xFF
+    }
+}
+
+// SB and SC. A transfer shifts SB out one bit at a time while the partner's byte shifts in,
+// then requests the serial interrupt.
+pub struct Serial {
+    pub sb: u8,
+    sc: u8,
+    endpoint: Box<dyn SerialEndpoint>,
+    // Partner's byte still to be shifted into SB
+    incoming: u8,
+    bits_left: u8,
+    // M-cycles until the next bit is shifted
+    bit_timer: u16,
+    // M-cycles until the endpoint is polled again
+    poll_timer: u16,
+    // M-cycles since power on
+    cycles: u64,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            sb: 0x00,
+            sc: 0x00,
+            endpoint: Box::new(Disconnected),
+            incoming: 0xFF,
+            bits_left: 0,
+            bit_timer: 0,
+            poll_timer: POLL_INTERVAL,
+            cycles: 0,
+        }
+    }
+
+    pub fn connect(&mut self, endpoint: Box<dyn SerialEndpoint>) {
+        self.endpoint = endpoint;
+    }
+
+    fn transferring(&self) -> bool {
+        self.sc & TRANSFER_START != 0
+    }
+
+    // Advances the serial port by a number of M-cycles, returns true if the serial interrupt was requested
+    pub fn tick(&mut self, cycles: u8) -> bool {
+        self.cycles += cycles as u64;
+        let mut interrupt = false;
+
+        if self.transferring() && self.bits_left > 0 {
+            let done = self.shift_bits(cycles);
+            if done {
+                self.finish_transfer();
+                interrupt = true;
+            }
+        }
+
+        // The partner can clock a transfer at any time, so it's polled even when we're not waiting
+        self.poll_timer = self.poll_timer.saturating_sub(cycles as u16);
+        if self.poll_timer == 0 {
+            self.poll_timer = POLL_INTERVAL;
+            self.endpoint.sync(self.cycles);
+
+            let external = self.sc & INTERNAL_CLOCK == 0;
+            let waiting = (self.transferring() && external && self.bits_left == 0).then_some(self.sb);
+            if let (Some(data), Some(_)) = (self.endpoint.external_transfer(waiting), waiting) {
+                // The partner's clock shifts the bits in at the same rate as our own
+                self.start_shifting(data);
+            }
+        }
+
+        interrupt
+    }
+
+    fn start_shifting(&mut self, incoming: u8) {
+        self.incoming = incoming;
+        self.bits_left = 8;
+        self.bit_timer = CYCLES_PER_BIT;
+    }
+
+    // Shifts the partner's byte into SB, returns true once all 8 bits are in
+    fn shift_bits(&mut self, cycles: u8) -> bool {
+        for _ in 0..cycles {
+            self.bit_timer -= 1;
+            if self.bit_timer == 0 {
+                self.bit_timer = CYCLES_PER_BIT;
+                self.sb = (self.sb << 1) | (self.incoming >> 7);
+                self.incoming <<= 1;
+                self.bits_left -= 1;
+                if self.bits_left == 0 {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn finish_transfer(&mut self) {
+        self.sc &= !TRANSFER_START;
+    }
+}
+
+impl IoDevice for Serial {
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            SB => self.sb,
+            SC => self.sc | 0x7E,
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        match address {
+            SB => self.sb = data,
+            SC => {
+                self.sc = data & (TRANSFER_START | INTERNAL_CLOCK);
+                self.bits_left = 0;
+                if self.transferring() && self.sc & INTERNAL_CLOCK != 0 {
+                    let incoming = self.endpoint.transfer(self.sb);
+                    self.start_shifting(incoming);
+                }
+                else if self.transferring() {
+                    // Let the partner know we're ready straight away
+                    self.poll_timer = 1;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::gameboy::Gameboy;
+    use crate::interrupt::INTERRUPT_FLAG;
+
+    // Keeps every byte sent in memory. Clones share the same buffer, so a test can hold on to one
+    // after plugging another in.
+    #[derive(Clone, Default)]
+    pub struct BufferEndpoint {
+        sent: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl BufferEndpoint {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn bytes(&self) -> Vec<u8> {
+            self.sent.borrow().clone()
+        }
+
+        pub fn text(&self) -> String {
+            String::from_utf8_lossy(&self.sent.borrow()).into_owned()
+        }
+    }
+
+    impl SerialEndpoint for BufferEndpoint {
+        fn transfer(&mut self, data: u8) -> u8 {
+            self.sent.borrow_mut().push(data);
+            0xFF
+        }
+    }
+
+    // Partner that clocks a transfer of its own on the first poll we're waiting on it
+    struct ClockingPartner {
+        data: u8,
+        received: Rc<RefCell<Option<u8>>>,
+    }
+
+    impl SerialEndpoint for ClockingPartner {
+        fn transfer(&mut self, _data: u8) -> u8 {
+            0xFF
+        }
+
+        fn external_transfer(&mut self, data: Option<u8>) -> Option<u8> {
+            data?;
+            *self.received.borrow_mut() = data;
+            Some(self.data)
+        }
+    }
+
+    #[test]
+    fn internal_clock_transfer() {
+        // Set up serial for test
+        let mut serial = Serial::new();
+        let buffer = BufferEndpoint::new();
+        serial.connect(Box::new(buffer.clone()));
+
+        // Run test and compare output, 8 bits at 128 M-cycles each
+        serial.write(SB, 0x41);
+        serial.write(SC, 0x81);
+        assert_eq!(buffer.bytes(), [0x41]);
+        assert_eq!(serial.read(SC), 0xFF);
+        assert_eq!(serial.tick(128), false);
+        assert_eq!(serial.read(SB), 0x83);
+        for _ in 0..1023 - 128 {
+            assert_eq!(serial.tick(1), false);
+        }
+        assert_eq!(serial.tick(1), true);
+        assert_eq!(serial.read(SB), 0xFF);
+        assert_eq!(serial.read(SC), 0x7F);
+        assert_eq!(serial.tick(200), false);
+    }
+
+    #[test]
+    fn external_clock_waits() {
+        // Set up serial for test, nothing on the other end ever clocks the transfer
+        let mut serial = Serial::new();
+        serial.write(SB, 0x12);
+        serial.write(SC, 0x80);
+
+        // Run test and compare output
+        for _ in 0..100 {
+            assert_eq!(serial.tick(255), false);
+        }
+        assert_eq!(serial.read(SC), 0xFE);
+        assert_eq!(serial.read(SB), 0x12);
+    }
+
+    #[test]
+    fn external_clock_partner() {
+        // Set up serial for test
+        let mut serial = Serial::new();
+        let received = Rc::new(RefCell::new(None));
+        serial.connect(Box::new(ClockingPartner { data: 0x5A, received: received.clone() }));
+        serial.write(SB, 0x12);
+        serial.write(SC, 0x80);
+
+        // Run test and compare output, the partner's clock shifts the byte in over 1024 M-cycles
+        assert_eq!(serial.tick(1), false);
+        for _ in 0..1023 {
+            assert_eq!(serial.tick(1), false);
+        }
+        assert_eq!(serial.tick(1), true);
+        assert_eq!(serial.read(SB), 0x5A);
+        assert_eq!(serial.read(SC), 0x7E);
+        assert_eq!(*received.borrow(), Some(0x12));
+    }
+
+    #[test]
+    fn serial_interrupt_requested() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+        let buffer = BufferEndpoint::new();
+        gameboy.connect_serial(Box::new(buffer.clone()));
+
+        // Set up gameboy state for test, prints "ok" the way Blargg's ROMs do
+        let program = [
+            0x3E, b'o', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02,     // ld a, 'o'; ldh (SB), a; ld a, $81; ldh (SC), a
+            0xF0, 0x02, 0x17, 0x38, 0xFB,                       // ldh a, (SC); rla; jr c, -5
+            0x3E, b'k', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02,     // ld a, 'k'; ldh (SB), a; ld a, $81; ldh (SC), a
+            0x18, 0xFE,                                         // jr -2
+        ];
+        for (offset, byte) in program.iter().enumerate() {
+            gameboy.write_instruction(0xC000 + offset as u16, *byte);
+        }
+        gameboy.cpu.register.pc = 0xC000;
+
+        // Run test and compare output
+        for _ in 0..1000 {
+            gameboy.fetch();
+        }
+        assert_eq!(buffer.text(), "ok");
+        assert_eq!(gameboy.read_instruction(INTERRUPT_FLAG) & 0x08, 0x08);
+    }
+}
@@ -0,0 +1,119 @@
+// Helpers shared between tests, mostly for running the public hardware test suites under
+// `cargo test`. The ROMs themselves aren't redistributed with the emulator, so tests using them
+// are marked ignored.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use crate::cartridge::Cartridge;
+use crate::cpu::RegisterU8;
+use crate::gameboy::Gameboy;
+use crate::ppu::Renderer;
+use crate::serial::tests::BufferEndpoint;
+
+// Enough for any mooneye test to finish, they take a few seconds of emulated time at most
+const MAX_CYCLES: u64 = 100_000_000;
+
+// Runs a mooneye test until it signals completion with LD B, B. Passing tests leave the
+// Fibonacci numbers 3, 5, 8, 13, 21, 34 in B, C, D, E, H and L.
+pub fn run_mooneye(path: &str) -> bool {
+    let mut gameboy = load_test_rom(path);
+
+    if !run_until_ld_b_b(&mut gameboy) {
+        return false;
+    }
+
+    let register = &gameboy.cpu.register;
+    [RegisterU8::B, RegisterU8::C, RegisterU8::D, RegisterU8::E, RegisterU8::H, RegisterU8::L]
+        .into_iter()
+        .map(|reg| register.read_u8(reg))
+        .eq([3, 5, 8, 13, 21, 34])
+}
+
+// Runs one of Blargg's test ROMs and returns what it printed over the serial port. They finish
+// by printing "Passed" or "Failed" and then loop forever.
+pub fn run_blargg(path: &str) -> String {
+    let mut gameboy = load_test_rom(path);
+    let output = BufferEndpoint::new();
+    gameboy.connect_serial(Box::new(output.clone()));
+
+    let mut checked = 0;
+    while gameboy.cycles < MAX_CYCLES {
+        gameboy.fetch();
+        if gameboy.cycles - checked > 1_000_000 {
+            checked = gameboy.cycles;
+            let text = output.text();
+            if text.contains("Passed") || text.contains("Failed") {
+                break;
+            }
+        }
+    }
+
+    output.text()
+}
+
+// Runs a screenshot test such as dmg-acid2 or mealybug-tearoom and compares the frame shown
+// once it signals completion with LD B, B against the reference image.
+pub fn run_screenshot(path: &str, reference: &str, renderer: Renderer) -> bool {
+    let mut gameboy = load_test_rom(path);
+    gameboy.memory.ppu.renderer = renderer;
+
+    if !run_until_ld_b_b(&mut gameboy) {
+        return false;
+    }
+
+    // Finish the frame being drawn so the whole screen comes from after the test finished
+    gameboy.memory.ppu.frame_ready = false;
+    while !gameboy.memory.ppu.frame_ready && gameboy.cycles < MAX_CYCLES {
+        gameboy.fetch();
+    }
+
+    gameboy.memory.ppu.framebuffer[..] == reference_shades(reference)[..]
+}
+
+fn load_test_rom(path: &str) -> Gameboy {
+    let rom = fs::read(path).unwrap_or_else(|error| panic!("{}: {}", path, error));
+    let cartridge = Cartridge::from_bytes(rom).unwrap_or_else(|error| panic!("{}: {}", path, error));
+
+    let mut gameboy = Gameboy::new();
+    gameboy.memory.load_cartridge(cartridge);
+    gameboy.skip_boot();
+    gameboy
+}
+
+fn run_until_ld_b_b(gameboy: &mut Gameboy) -> bool {
+    while gameboy.cycles < MAX_CYCLES {
+        if gameboy.read_instruction(gameboy.cpu.register.pc) == 0x40 {
+            return true;
+        }
+        gameboy.fetch();
+    }
+
+    false
+}
+
+// Decodes a reference screenshot into shades 0-3, the images use the greys FF, AA, 55 and 00
+fn reference_shades(path: &str) -> Vec<u8> {
+    let file = fs::File::open(path).unwrap_or_else(|error| panic!("{}: {}", path, error));
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().unwrap_or_else(|error| panic!("{}: {}", path, error));
+
+    let mut image = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut image).unwrap_or_else(|error| panic!("{}: {}", path, error));
+    let channels = info.color_type.samples();
+
+    image[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| 3 - pixel[0] / 0x55)
+        .collect()
+}
+
+// Fresh empty directory per test so tests running in parallel don't share files
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("gb_emulator_{}_{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -1,17 +1,306 @@
+use crate::mmu::IoDevice;
+
+pub const DIV: u16 = 0xFF04;
+pub const TIMA: u16 = 0xFF05;
+pub const TMA: u16 = 0xFF06;
+pub const TAC: u16 = 0xFF07;
+
+// DIV, TIMA, TMA and TAC built on the 16-bit system counter.
+// TIMA increments on a falling edge of the counter bit selected by TAC, so anything that
+// drops that bit (DIV writes, TAC writes) can produce an extra increment like on hardware.
 pub struct Timer {
-    pub div_clocksum: usize,
-    pub timer_clocksum: usize,
-    pub div_reg: u8,
-    pub tac_reg: u8,
+    // Internal counter in T-cycles, DIV is the upper byte
+    pub counter: u16,
+    pub tima: u8,
+    pub tma: u8,
+    pub tac: u8,
+    // TIMA overflowed during the last M-cycle and is reloaded from TMA on the next one
+    overflow_pending: bool,
+    // TIMA was reloaded during the current M-cycle, it ignores writes and follows writes to TMA
+    reloading: bool,
 }
 
 impl Timer {
     pub fn new() -> Self {
         Self {
-            div_clocksum: 0,
-            timer_clocksum: 0,
-            div_reg: 0,
-            tac_reg: 0
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            overflow_pending: false,
+            reloading: false,
+        }
+    }
+
+    // Advances the timer by a number of M-cycles, returns true if the timer interrupt was requested
+    pub fn tick(&mut self, cycles: u8) -> bool {
+        let mut interrupt = false;
+
+        for _ in 0..cycles {
+            self.reloading = self.overflow_pending;
+            if self.overflow_pending {
+                self.overflow_pending = false;
+                self.tima = self.tma;
+                interrupt = true;
+            }
+
+            let old_signal = self.timer_signal();
+            self.counter = self.counter.wrapping_add(4);
+            self.detect_falling_edge(old_signal);
+        }
+
+        interrupt
+    }
+
+    // The counter bit selected by TAC, ANDed with the timer enable bit
+    fn timer_signal(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0 => 9,
+            1 => 3,
+            2 => 5,
+            _ => 7
+        };
+
+        (self.tac & 0x04) != 0 && (self.counter >> bit) & 1 != 0
+    }
+
+    fn detect_falling_edge(&mut self, old_signal: bool) {
+        if old_signal && !self.timer_signal() {
+            let (tima, overflow) = self.tima.overflowing_add(1);
+            self.tima = tima;
+            self.overflow_pending = overflow;
+        }
+    }
+}
+
+impl IoDevice for Timer {
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            DIV => (self.counter >> 8) as u8,
+            TIMA => self.tima,
+            TMA => self.tma,
+            TAC => self.tac | 0xF8,
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        match address {
+            DIV => {
+                let old_signal = self.timer_signal();
+                self.counter = 0;
+                self.detect_falling_edge(old_signal);
+            },
+            // Writing TIMA in the cycle after an overflow cancels the reload, in the cycle of the
+            // reload itself the write is lost
+            TIMA if !self.reloading => {
+                self.overflow_pending = false;
+                self.tima = data;
+            },
+            TMA => {
+                self.tma = data;
+                if self.reloading {
+                    self.tima = data;
+                }
+            },
+            TAC => {
+                let old_signal = self.timer_signal();
+                self.tac = data & 0x07;
+                self.detect_falling_edge(old_signal);
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::Gameboy;
+    use crate::interrupt::INTERRUPT_FLAG;
+    use crate::test_roms::{run_blargg, run_mooneye};
+
+    #[test]
+    fn div_increments() {
+        let mut timer = Timer::new();
+
+        // DIV counts at 16384Hz, once every 64 M-cycles
+        timer.tick(63);
+        assert_eq!(timer.read(DIV), 0);
+        timer.tick(1);
+        assert_eq!(timer.read(DIV), 1);
+    }
+
+    #[test]
+    fn div_write_resets_counter() {
+        let mut timer = Timer::new();
+
+        timer.tick(200);
+        timer.write(DIV, 0x12);
+        assert_eq!(timer.read(DIV), 0);
+        assert_eq!(timer.counter, 0);
+    }
+
+    #[test]
+    fn tima_frequencies() {
+        // M-cycles per TIMA increment for each TAC clock select
+        for (tac, period) in [(0x04, 256), (0x05, 4), (0x06, 16), (0x07, 64)] {
+            let mut timer = Timer::new();
+            timer.write(TAC, tac);
+
+            for _ in 0..period - 1 {
+                timer.tick(1);
+            }
+            assert_eq!(timer.read(TIMA), 0);
+            timer.tick(1);
+            assert_eq!(timer.read(TIMA), 1);
+        }
+    }
+
+    #[test]
+    fn tima_disabled() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0x01);
+
+        timer.tick(100);
+        assert_eq!(timer.read(TIMA), 0);
+        assert_eq!(timer.read(TAC), 0xF9);
+    }
+
+    #[test]
+    fn tima_overflow_reload_delay() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0x05);
+        timer.write(TMA, 0xAB);
+        timer.write(TIMA, 0xFF);
+
+        // TIMA reads 0 for one M-cycle before TMA is loaded and the interrupt requested
+        assert_eq!(timer.tick(4), false);
+        assert_eq!(timer.read(TIMA), 0x00);
+        assert_eq!(timer.tick(1), true);
+        assert_eq!(timer.read(TIMA), 0xAB);
+    }
+
+    #[test]
+    fn tima_write_cancels_reload() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0x05);
+        timer.write(TMA, 0xAB);
+        timer.write(TIMA, 0xFF);
+
+        timer.tick(4);
+        timer.write(TIMA, 0x10);
+        assert_eq!(timer.tick(1), false);
+        assert_eq!(timer.read(TIMA), 0x10);
+    }
+
+    #[test]
+    fn tima_write_during_reload_ignored() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0x05);
+        timer.write(TMA, 0xAB);
+        timer.write(TIMA, 0xFF);
+
+        timer.tick(5);
+        timer.write(TIMA, 0x10);
+        assert_eq!(timer.read(TIMA), 0xAB);
+    }
+
+    #[test]
+    fn tma_write_during_reload() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0x05);
+        timer.write(TMA, 0xAB);
+        timer.write(TIMA, 0xFF);
+
+        // The new TMA goes straight through to TIMA in the reload cycle, but not after it
+        timer.tick(5);
+        timer.write(TMA, 0xCD);
+        assert_eq!(timer.read(TIMA), 0xCD);
+        timer.tick(1);
+        timer.write(TMA, 0x12);
+        assert_eq!(timer.read(TIMA), 0xCD);
+    }
+
+    #[test]
+    fn div_write_glitch() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0x05);
+
+        // Bit 3 of the counter is set, resetting it is seen as a falling edge
+        timer.tick(2);
+        assert_eq!(timer.read(TIMA), 0);
+        timer.write(DIV, 0);
+        assert_eq!(timer.read(TIMA), 1);
+    }
+
+    #[test]
+    fn tac_write_glitch() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0x05);
+
+        // Disabling the timer while the selected bit is high increments TIMA
+        timer.tick(2);
+        timer.write(TAC, 0x01);
+        assert_eq!(timer.read(TIMA), 1);
+    }
+
+    #[test]
+    fn timer_interrupt_requested() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test
+        for address in 0xC000..0xC008 {
+            gameboy.write_instruction(address, 0x00);
         }
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(TAC, 0x05);
+        gameboy.write_instruction(TIMA, 0xFF);
+
+        // Overflow after 4 M-cycles, interrupt one M-cycle later
+        for _ in 0..5 {
+            gameboy.fetch();
+        }
+        assert_eq!(gameboy.read_instruction(INTERRUPT_FLAG), 0xE4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn tima_read_mid_instruction() {
+        // Create a gameboy for testing purposes
+        let mut gameboy = Gameboy::new();
+
+        // Set up gameboy state for test, nop then ldh a, (TIMA)
+        gameboy.write_instruction(0xC000, 0x00);
+        gameboy.write_instruction(0xC001, 0xF0);
+        gameboy.write_instruction(0xC002, 0x05);
+        gameboy.cpu.register.pc = 0xC000;
+        gameboy.write_instruction(TAC, 0x05);
+
+        // Run test and compare output, TIMA ticks over on the third M-cycle of the ldh and the
+        // read in that same cycle sees it
+        gameboy.fetch();
+        gameboy.fetch();
+        assert_eq!(gameboy.cpu.register.a, 0x01);
+    }
+
+    #[test]
+    #[ignore = "needs Blargg's test ROMs in TEST/blargg"]
+    fn blargg_instr_timing() {
+        let output = run_blargg("TEST/blargg/instr_timing/instr_timing.gb");
+        assert_eq!(output.contains("Passed"), true, "{}", output);
+    }
+
+    #[test]
+    #[ignore = "needs the mooneye test suite in TEST/mooneye"]
+    fn mooneye_timer() {
+        for name in [
+            "div_write", "rapid_toggle", "tim00", "tim00_div_trigger", "tim01", "tim01_div_trigger", "tim10",
+            "tim10_div_trigger", "tim11", "tim11_div_trigger", "tima_reload", "tima_write_reloading",
+            "tma_write_reloading"
+        ] {
+            let path = format!("TEST/mooneye/acceptance/timer/{}.gb", name);
+            assert_eq!(run_mooneye(&path), true, "{}", name);
+        }
+    }
+}
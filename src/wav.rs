@@ -0,0 +1,246 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::apu::{Apu, SampleBuffer};
+
+const HEADER_SIZE: u32 = 44;
+// Bytes per stereo 16-bit sample
+const BLOCK_ALIGN: u16 = 4;
+
+// Writes stereo 16-bit PCM WAV files. The sizes in the header are filled in by `finish`.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    frames: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        WavWriter::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(writer: W, sample_rate: u32) -> io::Result<Self> {
+        let mut wav = Self {
+            writer,
+            sample_rate,
+            frames: 0,
+        };
+        wav.write_header()?;
+        Ok(wav)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let data_size = self.frames * BLOCK_ALIGN as u32;
+        let writer = &mut self.writer;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // PCM, two channels
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        writer.write_all(&(self.sample_rate * BLOCK_ALIGN as u32).to_le_bytes())?;
+        writer.write_all(&BLOCK_ALIGN.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())
+    }
+
+    pub fn write_sample(&mut self, sample: [i16; 2]) -> io::Result<()> {
+        self.writer.write_all(&sample[0].to_le_bytes())?;
+        self.writer.write_all(&sample[1].to_le_bytes())?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Writes out everything queued in a sample buffer
+    pub fn write_buffer(&mut self, buffer: &mut SampleBuffer) -> io::Result<()> {
+        while let Some(sample) = buffer.pop() {
+            self.write_sample(sample)?;
+        }
+        Ok(())
+    }
+
+    // Fills in the header now that the length is known and hands back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// Records the APU's mix, and optionally every channel as a separate stem
+pub struct AudioRecorder<W: Write + Seek> {
+    mix: WavWriter<W>,
+    stems: Option<[WavWriter<W>; 4]>,
+}
+
+impl AudioRecorder<BufWriter<File>> {
+    // Stems go next to the mix, `song.wav` gets `song_ch1.wav` to `song_ch4.wav`
+    pub fn create(path: &Path, sample_rate: u32, stems: bool) -> io::Result<Self> {
+        let mix = WavWriter::create(path, sample_rate)?;
+        if !stems {
+            return Ok(AudioRecorder::new(mix, None));
+        }
+
+        let stem_writers = [
+            WavWriter::create(&stem_path(path, 1), sample_rate)?,
+            WavWriter::create(&stem_path(path, 2), sample_rate)?,
+            WavWriter::create(&stem_path(path, 3), sample_rate)?,
+            WavWriter::create(&stem_path(path, 4), sample_rate)?,
+        ];
+        Ok(AudioRecorder::new(mix, Some(stem_writers)))
+    }
+}
+
+impl<W: Write + Seek> AudioRecorder<W> {
+    pub fn new(mix: WavWriter<W>, stems: Option<[WavWriter<W>; 4]>) -> Self {
+        Self { mix, stems }
+    }
+
+    // Moves the samples the APU has produced so far into the files
+    pub fn record(&mut self, apu: &mut Apu) -> io::Result<()> {
+        self.mix.write_buffer(&mut apu.samples)?;
+        if let (Some(writers), Some(buffers)) = (self.stems.as_mut(), apu.stems.as_mut()) {
+            for (writer, buffer) in writers.iter_mut().zip(buffers.iter_mut()) {
+                writer.write_buffer(buffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the mix writer followed by any stem writers
+    pub fn finish(self) -> io::Result<Vec<W>> {
+        let mut writers = vec![self.mix.finish()?];
+        if let Some(stems) = self.stems {
+            for stem in stems {
+                writers.push(stem.finish()?);
+            }
+        }
+        Ok(writers)
+    }
+}
+
+fn stem_path(path: &Path, channel: u8) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_ch{}.wav", stem, channel))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use crate::cartridge::tests::build_rom;
+    use crate::cartridge::Cartridge;
+    use crate::gameboy::Gameboy;
+
+    // Rate the golden files are recorded at, low to keep them small
+    const GOLDEN_SAMPLE_RATE: u32 = 8192;
+    const GOLDEN_FRAMES: u32 = 10;
+
+    #[test]
+    fn header() {
+        // Set up wav writer for test
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 44100).unwrap();
+        wav.write_sample([1, -1]).unwrap();
+        wav.write_sample([0x1234, 0]).unwrap();
+
+        // Run test and compare output
+        let bytes = wav.finish().unwrap().into_inner();
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(bytes[4..8], 44u32.to_le_bytes());
+        assert_eq!(bytes[24..28], 44100u32.to_le_bytes());
+        assert_eq!(bytes[40..44], 8u32.to_le_bytes());
+        assert_eq!(bytes[44..], [0x01, 0x00, 0xFF, 0xFF, 0x34, 0x12, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn stem_names() {
+        assert_eq!(stem_path(Path::new("out/song.wav"), 3), Path::new("out/song_ch3.wav"));
+    }
+
+    // Plays a chord on all four channels: a swept square, a square, a sawtooth and noise
+    fn music_rom() -> Vec<u8> {
+        let mut rom = build_rom(0x8000, 0x00, 0x00, 0x00);
+        let program = [
+            0x3E, 0xFF, 0xE0, 0x24,             // ld a, $FF; ldh (NR50), a
+            0xE0, 0x25,                         // ldh (NR51), a
+            0x3E, 0x16, 0xE0, 0x10,             // ld a, $16; ldh (NR10), a
+            0x3E, 0xF3, 0xE0, 0x12,             // ld a, $F3; ldh (NR12), a
+            0xAF, 0xE0, 0x13,                   // xor a; ldh (NR13), a
+            0x3E, 0x86, 0xE0, 0x14,             // ld a, $86; ldh (NR14), a
+            0x3E, 0xA7, 0xE0, 0x17,             // ld a, $A7; ldh (NR22), a
+            0x3E, 0x86, 0xE0, 0x19,             // ld a, $86; ldh (NR24), a
+            0x3E, 0x80, 0xE0, 0x1A,             // ld a, $80; ldh (NR30), a
+            0x3E, 0x20, 0xE0, 0x1C,             // ld a, $20; ldh (NR32), a
+            0xAF, 0xE0, 0x1D,                   // xor a; ldh (NR33), a
+            0x3E, 0x87, 0xE0, 0x1E,             // ld a, $87; ldh (NR34), a
+            0x3E, 0xF1, 0xE0, 0x21,             // ld a, $F1; ldh (NR42), a
+            0x3E, 0x80, 0xE0, 0x23,             // ld a, $80; ldh (NR44), a
+            0x18, 0xFE,                         // jr -2
+        ];
+        rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+        // jp $0150
+        rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        rom
+    }
+
+    fn record_music() -> Vec<Vec<u8>> {
+        let mut gameboy = Gameboy::new();
+        gameboy.memory.load_cartridge(Cartridge::from_bytes(music_rom()).unwrap());
+        gameboy.skip_boot();
+        // A rising sawtooth in wave RAM
+        for offset in 0..0x10 {
+            gameboy.write_instruction(0xFF30 + offset, offset as u8 * 0x11);
+        }
+        gameboy.memory.apu.set_sample_rate(GOLDEN_SAMPLE_RATE);
+        gameboy.memory.apu.enable_stems();
+
+        let writer = || WavWriter::new(Cursor::new(Vec::new()), GOLDEN_SAMPLE_RATE).unwrap();
+        let mut recorder = AudioRecorder::new(writer(), Some([writer(), writer(), writer(), writer()]));
+        for _ in 0..GOLDEN_FRAMES {
+            while !gameboy.memory.ppu.frame_ready {
+                gameboy.fetch();
+            }
+            gameboy.memory.ppu.frame_ready = false;
+            recorder.record(&mut gameboy.memory.apu).unwrap();
+        }
+
+        recorder.finish().unwrap().into_iter().map(Cursor::into_inner).collect()
+    }
+
+    #[test]
+    fn golden_music() {
+        // Set UPDATE_GOLDEN=1 to rewrite the files after an intended change to the output
+        let names = ["music.wav", "music_ch1.wav", "music_ch2.wav", "music_ch3.wav", "music_ch4.wav"];
+        let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+        // Run test and compare output
+        for (name, recorded) in names.iter().zip(record_music()) {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+            if update {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, &recorded).unwrap();
+                continue;
+            }
+
+            let golden = fs::read(&path).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+            assert_eq!(recorded.len(), golden.len(), "{}", name);
+            let mismatch = recorded.iter().zip(&golden).position(|(a, b)| a != b);
+            assert_eq!(mismatch, None, "{} differs from the golden file", name);
+        }
+    }
+}